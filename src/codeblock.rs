@@ -3,11 +3,18 @@ A code block in the source.
 */
 
 use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::Path;
 
 /**
 A code block in the source.
+
+The [`span`](Self::span) is not a part of the identity of a code block,
+i.e. the code blocks with the same content, language and attributes are equal wherever they are from.
 */
-#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone)]
 pub struct CodeBlock<'a> {
     /// The content of the code block.
     pub content: Cow<'a, str>,
@@ -15,6 +22,8 @@ pub struct CodeBlock<'a> {
     pub lang: Cow<'a, str>,
     /// The attributes of the code block.
     pub attrs: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    /// Where the code block is in the source, `None` if unknown.
+    pub span: Option<Span<'a>>,
 }
 
 impl<'a> CodeBlock<'a> {
@@ -28,6 +37,7 @@ impl<'a> CodeBlock<'a> {
             content: content.into(),
             lang: lang.into(),
             attrs,
+            span: None,
         }
     }
 
@@ -48,6 +58,44 @@ impl<'a> CodeBlock<'a> {
         self.attrs = attrs;
         self
     }
+
+    /// Set span and return self.
+    pub fn with_span(&mut self, span: Option<Span<'a>>) -> &mut Self {
+        self.span = span;
+        self
+    }
+
+    /// Get the fields identifying the code block, i.e. all but the span.
+    #[allow(clippy::type_complexity)]
+    fn identity(&self) -> (&str, &str, &[(Cow<'a, str>, Cow<'a, str>)]) {
+        (&self.content, &self.lang, &self.attrs)
+    }
+}
+
+impl PartialEq for CodeBlock<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for CodeBlock<'_> {}
+
+impl Hash for CodeBlock<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity().hash(state);
+    }
+}
+
+impl PartialOrd for CodeBlock<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CodeBlock<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.identity().cmp(&other.identity())
+    }
 }
 
 impl Default for CodeBlock<'_> {
@@ -55,3 +103,42 @@ impl Default for CodeBlock<'_> {
         Self::new("", "", vec![])
     }
 }
+
+
+
+/**
+The position of a code block in its source.
+
+The position is where the content of the code block starts and ends,
+so that the first line of the content is at [`line`](Self::line);
+but for sources whose readers can't tell where the content is,
+it may be where the whole element containing the content is.
+
+The spans can be computed from byte offsets with [`LineIndex`](crate::read::LineIndex).
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span<'a> {
+    /// The path to the source file, `None` if the source is not read from a file.
+    pub path: Option<Cow<'a, Path>>,
+    /// The byte offset of the start in the source.
+    pub start: usize,
+    /// The byte offset of the end in the source, exclusive.
+    pub end: usize,
+    /// The line of the start, starting from 1.
+    pub line: usize,
+    /// The column of the start in characters, starting from 1.
+    pub column: usize,
+}
+
+impl<'a> Span<'a> {
+    /// The byte range in the source.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Set path and return self.
+    pub fn with_path(&mut self, path: Option<Cow<'a, Path>>) -> &mut Self {
+        self.path = path;
+        self
+    }
+}
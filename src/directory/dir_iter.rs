@@ -7,7 +7,7 @@ An iterator over all files in a directory in the filesystem.
 use std::fs::{read_dir, ReadDir};
 use std::io::Error;
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::read::SourceCode;
 
@@ -15,9 +15,13 @@ use crate::read::SourceCode;
 
 /**
 An iterator over all files in a directory in the filesystem.
+
+It yields the path to each file relative to the root, and the path to it as the source.
 */
 #[derive(Debug)]
 pub struct DirIter<'a> {
+    /// The root of the directory, which the yielded paths are relative to.
+    root: PathBuf,
     /// All iterating directories, in a stack.
     state: Vec<ReadDir>,
     /// We need to return a type of such lifetime bound, make the rustc happy.
@@ -28,6 +32,7 @@ impl DirIter<'_> {
     /// Create an iterator in the directory at `path`.
     pub fn new(path: &Path) -> Result<Self, Error> {
         Ok(Self {
+            root: path.to_path_buf(),
             state: vec![read_dir(path)?],
             phantom: PhantomData,
         })
@@ -36,7 +41,7 @@ impl DirIter<'_> {
 
 impl<'a> Iterator for DirIter<'a> {
     // The result item is owned
-    type Item = Result<SourceCode<'a, 'a>, Error>;
+    type Item = Result<(PathBuf, SourceCode<'a, 'a>), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let item = match self.state.last_mut().expect("State shouldn't be empty").next() {
@@ -54,7 +59,12 @@ impl<'a> Iterator for DirIter<'a> {
             });
             self.next()
         } else {
-            Some(Ok(item.path().into()))
+            let path = item.path();
+            let rel = path
+                .strip_prefix(&self.root)
+                .expect("Files are in the root")
+                .to_path_buf();
+            Some(Ok((rel, path.into())))
         }
     }
 }
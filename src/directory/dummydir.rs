@@ -65,16 +65,16 @@ impl Default for DummyDir {
     }
 }
 
-/// Convert the pair to its wrapping source code string,
+/// Convert the pair to the path and its wrapping source code string,
 /// so that we can write out the type of `DummyDir::DirIter`.
-fn conv_elem_to_str<'a, T>(x: (&T, &'a Vec<u8>)) -> Result<SourceCode<'a, 'a>, Utf8Error> {
-    Ok(std::str::from_utf8(x.1)?.into())
+fn conv_elem_to_str<'a>(x: (&PathBuf, &'a Vec<u8>)) -> Result<(PathBuf, SourceCode<'a, 'a>), Utf8Error> {
+    Ok((x.0.clone(), std::str::from_utf8(x.1)?.into()))
 }
 
 impl Directory for DummyDir {
     type DirIter<'a> = std::iter::Map<
         std::collections::hash_map::Iter<'a, PathBuf, Vec<u8>>,
-        fn((&PathBuf, &'a Vec<u8>)) -> Result<SourceCode<'a, 'a>, Utf8Error>,
+        fn((&PathBuf, &'a Vec<u8>)) -> Result<(PathBuf, SourceCode<'a, 'a>), Utf8Error>,
     >;
     type OpenError = !;
     type WalkError = Utf8Error;
@@ -84,6 +84,16 @@ impl Directory for DummyDir {
         Ok(self.ctnt.entry(path.to_path_buf()).or_default())
     }
 
+    fn open_write<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        let res = self.ctnt.entry(path.to_path_buf()).or_default();
+        res.clear();
+        Ok(res)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Self::OpenError> {
+        Ok(self.ctnt.get(path).cloned())
+    }
+
     fn walk(&self) -> Result<Self::DirIter<'_>, Self::OpenError> {
         Ok(self.ctnt.iter().map(conv_elem_to_str))
     }
@@ -109,6 +119,17 @@ mod tests {
         assert_eq!(ctnt, [("a".into(), b"hello".to_vec()), ("b".into(), b"hi".to_vec())]);
     }
 
+    #[test]
+    fn write_read() {
+        let mut dir = DummyDir::new();
+        assert_eq!(dir.read_file(Path::new("a")).unwrap(), None);
+        dir.open_append(Path::new("a")).unwrap().write_all(b"hello").unwrap();
+        dir.open_append(Path::new("a")).unwrap().write_all(b" world").unwrap();
+        assert_eq!(dir.read_file(Path::new("a")).unwrap().unwrap(), b"hello world");
+        dir.open_write(Path::new("a")).unwrap().write_all(b"hi").unwrap();
+        assert_eq!(dir.read_file(Path::new("a")).unwrap().unwrap(), b"hi");
+    }
+
     #[test]
     fn walk() {
         let dir = DummyDir::with_ctnt([
@@ -117,6 +138,9 @@ mod tests {
         ]);
         let mut ctnt = dir.walk().unwrap().map(|x| x.unwrap()).collect::<Vec<_>>();
         ctnt.sort();
-        assert_eq!(ctnt, ["hello".into(), "hi".into()]);
+        assert_eq!(
+            ctnt,
+            [(PathBuf::from("a"), "hello".into()), (PathBuf::from("b"), "hi".into())]
+        );
     }
 }
@@ -0,0 +1,169 @@
+/*!
+A directory rooted at a path in the filesystem.
+*/
+
+
+
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use super::dir_iter::DirIter;
+use super::{normalize, Directory};
+
+
+
+/**
+A directory in the filesystem, rooted at an arbitrary path.
+
+The paths of the files are relative to the root,
+and the parent directories of the files, including the root itself, are created when opening the files.
+The root must exist before [walking](Directory::walk) the directory.
+Besides the lexical [normalization](Directory::normalize_path),
+the paths passing through symlinks leading outside the root are rejected too.
+
+Example:
+
+```no_run
+use scribere::directory::fsdir::FsDir;
+use scribere::dispatch::{ByAttr, DispatchErrless};
+use scribere::write_blocks_errless;
+# let blocks = Vec::<scribere::CodeBlock>::new();
+
+let mut dir = FsDir::new("target/tangled");
+write_blocks_errless(ByAttr::new("file").dispatch(blocks.into_iter()), &mut dir).unwrap();
+```
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FsDir {
+    /// The root of the directory.
+    root: PathBuf,
+}
+
+impl FsDir {
+    /// Construct a directory rooted at the path.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Get the root of the directory.
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Directory for FsDir {
+    type DirIter<'a> = DirIter<'a>;
+    type OpenError = std::io::Error;
+    type WalkError = std::io::Error;
+    type Writer<'a> = File;
+
+    fn open_append<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(prepare(&self.root, path)?)
+    }
+
+    fn open_write<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        File::create(prepare(&self.root, path)?)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Self::OpenError> {
+        match std::fs::read(self.root.join(path)) {
+            Ok(x) => Ok(Some(x)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn walk(&self) -> Result<Self::DirIter<'_>, Self::OpenError> {
+        DirIter::new(&self.root)
+    }
+
+    fn normalize_path(&self, path: &Path) -> Option<PathBuf> {
+        let res = normalize(path)?;
+        let Ok(root) = self.root.canonicalize() else {
+            // Nothing is in the root if it doesn't exist
+            return Some(res);
+        };
+        let mut cur = root.clone();
+        for comp in res.components() {
+            cur.push(comp);
+            match cur.symlink_metadata() {
+                Err(_) => break,
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    // Dangling symlinks fail to be canonicalized, and are rejected too
+                    cur = cur.canonicalize().ok().filter(|x| x.starts_with(&root))?;
+                }
+                Ok(_) => (),
+            }
+        }
+        Some(res)
+    }
+}
+
+/// Get the path to the file in the root, and create its parent directories.
+fn prepare(root: &Path, path: &Path) -> std::io::Result<PathBuf> {
+    let path = root.join(path);
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    Ok(path)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+    use std::io::Write;
+    use std::path::Path;
+
+    use tempfile::TempDir;
+
+    use super::FsDir;
+    use crate::directory::Directory;
+
+    #[test]
+    fn nested_root() {
+        let tmp = TempDir::new().unwrap();
+        let mut dir = FsDir::new(tmp.path().join("out/put"));
+        assert!(dir.walk().is_err());
+        dir.open_append(Path::new("a/b")).unwrap().write_all(b"hello").unwrap();
+        dir.open_append(Path::new("a/b")).unwrap().write_all(b" world").unwrap();
+        assert_eq!(read_to_string(tmp.path().join("out/put/a/b")).unwrap(), "hello world");
+        dir.open_write(Path::new("c")).unwrap().write_all(b"hi").unwrap();
+        assert_eq!(dir.read_file(Path::new("c")).unwrap().unwrap(), b"hi");
+        assert_eq!(dir.read_file(Path::new("d")).unwrap(), None);
+        let mut files = dir
+            .walk()
+            .unwrap()
+            .map(|x| {
+                let (rel, src) = x.unwrap();
+                assert_eq!(**src.as_file().unwrap(), dir.path().join(&rel));
+                rel
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, [Path::new("a/b"), Path::new("c")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let tmp = TempDir::new().unwrap();
+        let dir = FsDir::new(tmp.path().join("root"));
+        assert_eq!(dir.normalize_path(Path::new("a/./b/../c")).unwrap(), Path::new("a/c"));
+        assert_eq!(dir.normalize_path(Path::new("../x")), None);
+        std::fs::create_dir_all(tmp.path().join("root/a")).unwrap();
+        symlink(tmp.path(), tmp.path().join("root/out")).unwrap();
+        symlink(tmp.path().join("root/a"), tmp.path().join("root/in")).unwrap();
+        symlink(tmp.path().join("nowhere"), tmp.path().join("root/dangling")).unwrap();
+        assert_eq!(dir.normalize_path(Path::new("out/x")), None);
+        assert_eq!(dir.normalize_path(Path::new("dangling")), None);
+        assert_eq!(dir.normalize_path(Path::new("in/x")).unwrap(), Path::new("in/x"));
+        assert_eq!(dir.normalize_path(Path::new("out/../a/x")).unwrap(), Path::new("a/x"));
+    }
+}
@@ -5,7 +5,7 @@ The directories to store extracted codes in files.
 
 
 use std::io::Write;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 use crate::read::SourceCode;
 
@@ -25,14 +25,73 @@ pub trait Directory {
     type OpenError: std::error::Error;
     /// Open a file at a path relative to the directory for appending.
     fn open_append<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError>;
+    /// Open a file at a path relative to the directory for writing, truncating its content.
+    ///
+    /// The default implementation opens the file for [appending](Self::open_append),
+    /// which is only correct for the directories truncating the files there.
+    fn open_write<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        self.open_append(path)
+    }
+    /// Read the content of the file at a path relative to the directory, `None` if it doesn't exist.
+    ///
+    /// The default implementation returns `None` for all files,
+    /// so they are always reported as [`Created`](crate::write_blocks::FileStatus::Created)
+    /// when writing only the changed files.
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Self::OpenError> {
+        let _ = path;
+        Ok(None)
+    }
     /// Errors during walking the directory.
     type WalkError: std::error::Error;
-    /// The iterator over the files in the directory.
-    type DirIter<'a>: Iterator<Item = Result<SourceCode<'a, 'a>, Self::WalkError>>
+    /// The iterator over the files in the directory,
+    /// yielding the path relative to the directory and the source of each file.
+    type DirIter<'a>: Iterator<Item = Result<(PathBuf, SourceCode<'a, 'a>), Self::WalkError>>
     where
         Self: 'a;
     /// Get the iterator over the directory content.
     fn walk(&self) -> Result<Self::DirIter<'_>, Self::OpenError>;
+    /// Normalize the path relative to the directory,
+    /// `None` if it would escape the directory and thus must not be opened.
+    ///
+    /// The default implementation normalizes the path [lexically](normalize);
+    /// directories in the filesystem should also check that no symlink in the path leads outside.
+    fn normalize_path(&self, path: &Path) -> Option<PathBuf> {
+        normalize(path)
+    }
+}
+
+/**
+Normalize the relative path lexically, i.e. without accessing the filesystem.
+
+`.` is removed and `..` removes the component before it.
+Returns `None` if the path is absolute, `..` goes above the beginning, or the result is empty.
+
+Example:
+
+```
+use scribere::directory::normalize;
+use std::path::Path;
+
+assert_eq!(normalize(Path::new("./a/../b/c")).unwrap(), Path::new("b/c"));
+assert_eq!(normalize(Path::new("a/../../b")), None);
+assert_eq!(normalize(Path::new("/etc/passwd")), None);
+```
+*/
+pub fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut res = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::Normal(x) => res.push(x),
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !res.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!res.as_os_str().is_empty()).then_some(res)
 }
 
 
@@ -40,6 +99,7 @@ pub trait Directory {
 pub mod dir_iter;
 
 pub mod dummydir;
+pub mod fsdir;
 #[cfg(feature = "dir_tmpdir")]
 #[doc(cfg(feature = "dir_tmpdir"))]
 pub mod tmpdir;
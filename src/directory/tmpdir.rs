@@ -1,14 +1,18 @@
 /*!
 Implement [`Directory`](super::Directory) for [`tempfile::TempDir`].
+
+It behaves the same as a [`FsDir`] rooted at the temporary directory.
 */
 
 
 
-use std::fs::{create_dir_all, File};
+use std::fs::File;
+use std::path::{Path, PathBuf};
 
 use tempfile::TempDir;
 
 use super::dir_iter::DirIter;
+use super::fsdir::FsDir;
 use super::Directory;
 
 
@@ -19,28 +23,42 @@ impl Directory for TempDir {
     type WalkError = std::io::Error;
     type Writer<'a> = File;
 
-    fn open_append<'a>(&'a mut self, path: &std::path::Path) -> Result<Self::Writer<'a>, Self::OpenError> {
-        let path = self.path().join(path);
-        create_dir_all(path.parent().expect("The TempDir and its file should have a path"))?;
-        File::create(path)
+    fn open_append<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        FsDir::new(self.path()).open_append(path)
+    }
+
+    fn open_write<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        FsDir::new(self.path()).open_write(path)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Self::OpenError> {
+        FsDir::new(self.path()).read_file(path)
     }
 
     fn walk(&self) -> Result<Self::DirIter<'_>, Self::OpenError> {
         DirIter::new(self.path())
     }
+
+    fn normalize_path(&self, path: &Path) -> Option<PathBuf> {
+        FsDir::new(self.path()).normalize_path(path)
+    }
 }
 
 
 
 #[cfg(test)]
 mod tests {
-    use std::fs::read_to_string;
+    use std::fs::{read, read_to_string};
     use std::io::Write;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
     use tempfile::TempDir;
 
+    use crate::codeblock::CodeBlock;
+    use crate::directory::dummydir::DummyDir;
     use crate::directory::Directory;
+    use crate::dispatch::{ByAttr, DispatchErrless};
+    use crate::write_blocks::write_blocks_errless;
 
     #[test]
     fn new_file() {
@@ -54,6 +72,54 @@ mod tests {
         f.write_all(b"File b").unwrap();
         drop(f);
         assert_eq!(read_to_string(base.join("a/b")).unwrap(), "File b");
+        let mut f = dir.open_append(Path::new("a/b")).unwrap();
+        f.write_all(b" appended").unwrap();
+        drop(f);
+        assert_eq!(read_to_string(base.join("a/b")).unwrap(), "File b appended");
+    }
+
+    #[test]
+    fn same_as_dummy() {
+        let ctnt = [
+            ("a", "hello"),
+            ("b", "hello"),
+            ("a", "hi"),
+            ("a", "hello"),
+            ("b", "hello"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (k, v))| CodeBlock::new(format!("block {}\n", i + 1), "", vec![(k.into(), v.into())]))
+        .collect::<Vec<_>>();
+        let mut tmp = TempDir::new().unwrap();
+        let mut dummy = DummyDir::new();
+        // Write twice to make sure the files are truncated in each session
+        for _ in 0..2 {
+            write_blocks_errless(ByAttr::new("a").dispatch(ctnt.clone().into_iter()), &mut tmp).unwrap();
+            write_blocks_errless(ByAttr::new("a").dispatch(ctnt.clone().into_iter()), &mut dummy).unwrap();
+        }
+        let mut dummy = dummy.into_iter().collect::<Vec<_>>();
+        dummy.sort();
+        assert_eq!(
+            dummy,
+            [
+                (PathBuf::from("hello"), b"block 1\nblock 2\nblock 4\nblock 5\n".to_vec()),
+                (PathBuf::from("hi"), b"block 3\n".to_vec()),
+            ]
+        );
+        for (path, ctnt) in &dummy {
+            assert_eq!(&read(tmp.path().join(path)).unwrap(), ctnt);
+        }
+    }
+
+    #[test]
+    fn write_read() {
+        let mut dir = TempDir::new().unwrap();
+        assert_eq!(dir.read_file(Path::new("a/b")).unwrap(), None);
+        dir.open_write(Path::new("a/b")).unwrap().write_all(b"hello").unwrap();
+        assert_eq!(dir.read_file(Path::new("a/b")).unwrap().unwrap(), b"hello");
+        dir.open_write(Path::new("a/b")).unwrap().write_all(b"hi").unwrap();
+        assert_eq!(dir.read_file(Path::new("a/b")).unwrap().unwrap(), b"hi");
     }
 
     #[test]
@@ -79,9 +145,12 @@ mod tests {
             .walk()
             .unwrap()
             .map(|x| {
-                let mut path = x.unwrap().as_file().unwrap().to_str().unwrap().to_string();
+                let (rel, src) = x.unwrap();
+                let mut path = src.as_file().unwrap().to_str().unwrap().to_string();
                 assert!(path.starts_with(&base));
-                path.split_off(base.len() + 1)
+                let path = path.split_off(base.len() + 1);
+                assert_eq!(rel, Path::new(&path));
+                path
             })
             .collect::<Vec<_>>();
         ctnt.sort();
@@ -0,0 +1,285 @@
+/*!
+Convert the stateful event iterators into iterators with absolute targets, and back.
+*/
+
+
+
+use std::borrow::Cow;
+use std::iter::Map;
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::Event;
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+The error during [absolutizing](Absolutize).
+*/
+#[derive(Debug, Error)]
+pub enum AbsoluteError<B: std::error::Error> {
+    /// The target of the first code block is `None`.
+    #[error("the target of the first code block is `None`")]
+    NullPath,
+    /// The error while iterating the events.
+    #[error("iterating events error: {0}")]
+    BlockError(B),
+}
+
+/**
+A code block with its resolved target.
+
+Unlike [`Event`], the target is always present, so the items are independent of each other.
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AbsEvent<'a> {
+    /// Target file path of the code block.
+    pub target: Cow<'a, Path>,
+    /// Code block in the source.
+    pub block: CodeBlock<'a>,
+}
+
+
+
+/**
+The iterator type returned by [`Absolutize::absolutize`],
+see [the document of the trait](Absolutize) for more.
+*/
+#[derive(Debug, Clone)]
+pub struct AbsolutizeIter<'a, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<Event<'a>, E>>,
+{
+    /// The underlying iterator.
+    iter: It,
+    /// The target of the last event.
+    last: Option<Cow<'a, Path>>,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for AbsolutizeIter<'a, It, E>
+where
+    It: Iterator<Item = Result<Event<'a>, E>>,
+{
+    type Item = Result<AbsEvent<'a>, AbsoluteError<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = match self.iter.next()? {
+            Ok(x) => x,
+            Err(e) => return Some(Err(AbsoluteError::BlockError(e))),
+        };
+        if event.target.is_some() {
+            self.last = event.target;
+        }
+        Some(match self.last {
+            Some(ref target) => Ok(AbsEvent {
+                target: target.clone(),
+                block: event.block,
+            }),
+            None => Err(AbsoluteError::NullPath),
+        })
+    }
+}
+
+/**
+Attach the resolved target to every event in the iterator.
+
+The events in the result are independent of each other,
+so they can be filtered, sorted or grouped by the standard iterator tools,
+and then be converted back to the stateful events with [`Relativize`].
+An [`AbsoluteError::NullPath`] is returned for the events before the first target.
+
+Example:
+
+```
+use scribere::dispatch::{AbsolutizeErrless, Event, RelativizeErrless};
+use scribere::CodeBlock;
+use std::path::Path;
+
+let ctnt = [
+    Event::new_some(Path::new("b"), CodeBlock::new("1", "", vec![])),
+    Event::new_some(Path::new("a"), CodeBlock::new("2", "", vec![])),
+    Event::new_some(Path::new("b"), CodeBlock::new("3", "", vec![])),
+    Event::new_none(CodeBlock::new("4", "", vec![])),
+];
+let mut abs = ctnt.into_iter().absolutize().collect::<Result<Vec<_>, _>>().unwrap();
+abs.sort_by(|x, y| x.target.cmp(&y.target));
+assert_eq!(
+    abs.into_iter().relativize().collect::<Vec<_>>(),
+    [
+        Event::new_some(Path::new("a"), CodeBlock::new("2", "", vec![])),
+        Event::new_some(Path::new("b"), CodeBlock::new("1", "", vec![])),
+        Event::new_none(CodeBlock::new("3", "", vec![])),
+        Event::new_none(CodeBlock::new("4", "", vec![])),
+    ]
+);
+```
+*/
+pub trait Absolutize<'a, E: std::error::Error>: Iterator<Item = Result<Event<'a>, E>> + Sized {
+    /// Attach the resolved target to every event in the iterator.
+    ///
+    /// See [the trait document](Self) for more.
+    fn absolutize(self) -> AbsolutizeIter<'a, Self, E>;
+}
+
+impl<'a, E: std::error::Error, It: Iterator<Item = Result<Event<'a>, E>>> Absolutize<'a, E> for It {
+    fn absolutize(self) -> AbsolutizeIter<'a, Self, E> {
+        AbsolutizeIter { iter: self, last: None }
+    }
+}
+
+/**
+Attach the resolved target to every event in the errorless iterator.
+
+See [the document of `Absolutize`](Absolutize) for more.
+*/
+pub trait AbsolutizeErrless<'a>: Iterator<Item = Event<'a>> + Sized {
+    /// Attach the resolved target to every event in the iterator.
+    ///
+    /// See [the document of `Absolutize`](Absolutize) for more.
+    #[allow(clippy::type_complexity)]
+    fn absolutize(self) -> AbsolutizeIter<'a, Map<Self, fn(Event<'a>) -> Result<Event<'a>, !>>, !>;
+}
+
+impl<'a, It: Iterator<Item = Event<'a>>> AbsolutizeErrless<'a> for It {
+    fn absolutize(self) -> AbsolutizeIter<'a, Map<Self, fn(Event<'a>) -> Result<Event<'a>, !>>, !> {
+        AbsolutizeIter {
+            iter: self.map(Ok),
+            last: None,
+        }
+    }
+}
+
+
+
+/**
+The iterator type returned by [`Relativize::relativize`],
+see [the document of the trait](Relativize) for more.
+*/
+#[derive(Debug, Clone)]
+pub struct RelativizeIter<'a, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<AbsEvent<'a>, E>>,
+{
+    /// The underlying iterator.
+    iter: It,
+    /// The target of the last event.
+    last: Option<Cow<'a, Path>>,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for RelativizeIter<'a, It, E>
+where
+    It: Iterator<Item = Result<AbsEvent<'a>, E>>,
+{
+    type Item = Result<Event<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let AbsEvent { target, block } = match self.iter.next()? {
+            Ok(x) => x,
+            Err(e) => return Some(Err(e)),
+        };
+        let target = if self.last.as_ref() == Some(&target) {
+            None
+        } else {
+            self.last = Some(target.clone());
+            Some(target)
+        };
+        Some(Ok(Event { target, block }))
+    }
+}
+
+/**
+Convert the events with absolute targets back to the stateful events,
+where the target is present only when it changes.
+
+See [the document of `Absolutize`](Absolutize) for more.
+*/
+pub trait Relativize<'a, E: std::error::Error>: Iterator<Item = Result<AbsEvent<'a>, E>> + Sized {
+    /// Convert the events with absolute targets back to the stateful events.
+    ///
+    /// See [the trait document](Self) for more.
+    fn relativize(self) -> RelativizeIter<'a, Self, E>;
+}
+
+impl<'a, E: std::error::Error, It: Iterator<Item = Result<AbsEvent<'a>, E>>> Relativize<'a, E> for It {
+    fn relativize(self) -> RelativizeIter<'a, Self, E> {
+        RelativizeIter { iter: self, last: None }
+    }
+}
+
+/**
+The iterator type returned by [`RelativizeErrless::relativize`],
+see [the document of `Relativize`](Relativize) for more.
+*/
+#[derive(Debug, Clone)]
+//# The type is not intended for human to use its content ><
+#[allow(clippy::type_complexity)]
+pub struct RelativizeErrlessIter<'a, It: Iterator<Item = AbsEvent<'a>>>(
+    RelativizeIter<'a, Map<It, fn(AbsEvent<'a>) -> Result<AbsEvent<'a>, !>>, !>,
+);
+
+impl<'a, It: Iterator<Item = AbsEvent<'a>>> Iterator for RelativizeErrlessIter<'a, It> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|x| x.expect("Never type should never occur"))
+    }
+}
+
+/**
+Convert the errorless events with absolute targets back to the stateful events.
+
+See [the document of `Relativize`](Relativize) for more.
+*/
+pub trait RelativizeErrless<'a>: Iterator<Item = AbsEvent<'a>> + Sized {
+    /// Convert the events with absolute targets back to the stateful events.
+    ///
+    /// See [the document of `Relativize`](Relativize) for more.
+    fn relativize(self) -> RelativizeErrlessIter<'a, Self>;
+}
+
+impl<'a, It: Iterator<Item = AbsEvent<'a>>> RelativizeErrless<'a> for It {
+    fn relativize(self) -> RelativizeErrlessIter<'a, Self> {
+        RelativizeErrlessIter(RelativizeIter {
+            iter: self.map(Ok),
+            last: None,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{AbsoluteError, Absolutize, Relativize};
+    use crate::codeblock::CodeBlock;
+    use crate::dispatch::Event;
+
+    #[test]
+    fn round_trip() {
+        let ctnt = [
+            Ok(Event::new_none(CodeBlock::new("1", "", vec![]))),
+            Ok(Event::new_some(Path::new("a"), CodeBlock::new("2", "", vec![]))),
+            Err(std::fmt::Error),
+            Ok(Event::new_none(CodeBlock::new("3", "", vec![]))),
+            Ok(Event::new_some(Path::new("a"), CodeBlock::new("4", "", vec![]))),
+        ];
+        let abs = ctnt.into_iter().absolutize().collect::<Vec<_>>();
+        assert!(matches!(abs[0], Err(AbsoluteError::NullPath)));
+        assert!(matches!(abs[2], Err(AbsoluteError::BlockError(std::fmt::Error))));
+        let abs = abs.into_iter().filter(|x| !matches!(x, Err(AbsoluteError::NullPath)));
+        let res = abs.relativize().collect::<Vec<_>>();
+        assert!(matches!(
+            &res[..],
+            [
+                Ok(Event { target: Some(a), .. }),
+                Err(AbsoluteError::BlockError(_)),
+                Ok(Event { target: None, .. }),
+                Ok(Event { target: None, .. }),
+            ] if a == Path::new("a")
+        ));
+    }
+}
@@ -5,10 +5,10 @@ Dispatch the code blocks to files according to their attributes.
 
 
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use super::{Dispatch, Event};
+use super::{Dispatch, Event, TargetOf};
 use crate::codeblock::CodeBlock;
 
 
@@ -96,6 +96,15 @@ where
     }
 }
 
+impl TargetOf for ByAttr<'_> {
+    fn target_of<'a>(&'a self, blk: &CodeBlock<'a>) -> Option<Cow<'a, Path>> {
+        blk.attrs
+            .iter()
+            .find(|x| x.0 == self.name)
+            .map(|x| Cow::Owned(PathBuf::from(x.1.as_ref())))
+    }
+}
+
 impl Dispatch for ByAttr<'_> {
     type Output<'a, It, E: std::error::Error> = Iter<'a, It, E>
     where
@@ -0,0 +1,224 @@
+/*!
+Dispatch the code blocks to multiple files according to their attributes.
+*/
+
+
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use super::{Dispatch, Event};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Dispatch the code blocks to multiple files according to their attributes.
+
+Like [`ByAttr`](super::ByAttr), the targets are given by the attribute of the name,
+but all attributes of the name are taken, and each value is split by the separator (`,` by default)
+into several targets, with the spaces around them trimmed;
+so both `file=a.rs,b.rs` and `file=a.rs file=b.rs` dispatch the code block to `a.rs` and `b.rs`.
+When the attribute is absent, the code block is dispatched to the same files as the last code block.
+
+Since an [`Event`] has only one target, a code block with multiple targets is expanded
+into consecutive events with the same block, one for each target,
+so the code blocks are written to every target, with the order in each target kept.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::directory::dummydir::DummyDir;
+use scribere::dispatch::{ByAttrs, DispatchErrless, Event};
+use scribere::write_blocks_errless;
+use std::path::{Path, PathBuf};
+
+let disp = ByAttrs::new("file");
+let ctnt = [
+        CodeBlock::new("// License\n", "", vec![("file".into(), "a.rs, b.rs".into())]),
+        CodeBlock::new("fn a() {}\n", "", vec![("file".into(), "a.rs".into())]),
+        CodeBlock::new("fn b() {}\n", "", vec![("file".into(), "b.rs".into())]),
+    ];
+assert_eq!(
+    disp.dispatch(ctnt[..1].iter().cloned()).collect::<Vec<_>>(),
+    [
+        Event::new_some(Path::new("a.rs"), ctnt[0].clone()),
+        Event::new_some(Path::new("b.rs"), ctnt[0].clone()),
+    ]
+);
+let mut dir = DummyDir::new();
+write_blocks_errless(disp.dispatch(ctnt.into_iter()), &mut dir).unwrap();
+let mut files = dir.into_iter().collect::<Vec<_>>();
+files.sort();
+assert_eq!(
+    files,
+    [
+        (PathBuf::from("a.rs"), b"// License\nfn a() {}\n".to_vec()),
+        (PathBuf::from("b.rs"), b"// License\nfn b() {}\n".to_vec()),
+    ]
+);
+```
+*/
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByAttrs<'a> {
+    /// The name of the attribute indicating the destination files.
+    name: &'a str,
+    /// The separator between the targets in an attribute.
+    sep: Option<&'a str>,
+}
+
+impl<'a> ByAttrs<'a> {
+    /// Create a new dispatcher with the attribute name.
+    pub fn new(name: &'a str) -> Self {
+        Self { name, sep: Some(",") }
+    }
+
+    /// Set the separator between the targets in an attribute and return self,
+    /// `None` to take each attribute as a single target.
+    pub fn with_separator(&mut self, sep: Option<&'a str>) -> &mut Self {
+        self.sep = sep;
+        self
+    }
+
+    /// Get the targets of the code block, without duplicates.
+    fn targets_of(&self, blk: &CodeBlock) -> Vec<PathBuf> {
+        let mut res = Vec::<PathBuf>::new();
+        for (_, v) in blk.attrs.iter().filter(|x| x.0 == self.name) {
+            let targets: Box<dyn Iterator<Item = &str>> = match self.sep {
+                Some(sep) => Box::new(v.split(sep)),
+                None => Box::new([v.as_ref()].into_iter()),
+            };
+            for target in targets.map(str::trim).filter(|x| !x.is_empty()) {
+                if !res.iter().any(|x| x == target) {
+                    res.push(target.into());
+                }
+            }
+        }
+        res
+    }
+}
+
+/**
+The iterator returned by [`ByAttrs::dispatch`].
+
+See [the document of `Dispatch`](Dispatch) for more.
+*/
+#[derive(Debug, Clone)]
+pub struct Iter<'a, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    /// The dispatcher.
+    disp: ByAttrs<'a>,
+    /// The targets of the last code block.
+    targets: Vec<PathBuf>,
+    /// The target of the last event.
+    last: Option<PathBuf>,
+    /// The events expanded but not yet returned.
+    pending: VecDeque<Event<'a>>,
+    /// The underlying code block iterator.
+    iter: It,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for Iter<'a, It, E>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    type Item = Result<Event<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(Ok(event));
+        }
+        let blk = match self.iter.next()? {
+            Ok(blk) => blk,
+            Err(e) => return Some(Err(e)),
+        };
+        let targets = self.disp.targets_of(&blk);
+        if !targets.is_empty() {
+            self.targets = targets;
+        }
+        if self.targets.is_empty() {
+            return Some(Ok(Event::new_none(blk)));
+        }
+        for target in &self.targets {
+            let target = if self.last.as_ref() == Some(target) {
+                None
+            } else {
+                self.last = Some(target.clone());
+                Some(Cow::Owned(target.clone()))
+            };
+            self.pending.push_back(Event {
+                target,
+                block: blk.clone(),
+            });
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+impl Dispatch for ByAttrs<'_> {
+    type Output<'a, It, E: std::error::Error> = Iter<'a, It, E>
+    where
+        Self: 'a,
+        It: Iterator<Item = Result<CodeBlock<'a>, E>>;
+
+    fn dispatch<'a, It, E: std::error::Error>(&'a self, iter: It) -> Self::Output<'a, It, E>
+    where
+        It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+    {
+        Iter {
+            disp: *self,
+            targets: vec![],
+            last: None,
+            pending: VecDeque::new(),
+            iter,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::ByAttrs;
+    use crate::codeblock::CodeBlock;
+    use crate::dispatch::{DispatchErrless, Event};
+
+    #[test]
+    fn inherit() {
+        let ctnt = [
+            CodeBlock::new("0", "", vec![]),
+            CodeBlock::new("1", "", vec![("f".into(), "a".into()), ("f".into(), "b, a,,".into())]),
+            CodeBlock::new("2", "", vec![]),
+            CodeBlock::new("3", "", vec![("f".into(), "b".into())]),
+            CodeBlock::new("4", "", vec![]),
+        ];
+        assert_eq!(
+            ByAttrs::new("f").dispatch(ctnt.clone().into_iter()).collect::<Vec<_>>(),
+            [
+                Event::new_none(ctnt[0].clone()),
+                Event::new_some(Path::new("a"), ctnt[1].clone()),
+                Event::new_some(Path::new("b"), ctnt[1].clone()),
+                Event::new_some(Path::new("a"), ctnt[2].clone()),
+                Event::new_some(Path::new("b"), ctnt[2].clone()),
+                Event::new_none(ctnt[3].clone()),
+                Event::new_none(ctnt[4].clone()),
+            ]
+        );
+        assert_eq!(
+            ByAttrs::new("f")
+                .with_separator(None)
+                .dispatch(ctnt[1..2].iter().cloned())
+                .collect::<Vec<_>>(),
+            [
+                Event::new_some(Path::new("a"), ctnt[1].clone()),
+                Event::new_some(Path::new("b, a,,"), ctnt[1].clone()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+/*!
+Dispatch the code blocks to files according to their languages.
+*/
+
+
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{Dispatch, Event, TargetOf};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Dispatch the code blocks to files according to their [languages](CodeBlock::lang).
+
+The languages are mapped to the targets through a table.
+A code block in a language absent in the table is dispatched to the fallback target if it's given,
+otherwise it's dispatched to the same file as the last code block,
+or it's dropped if the dispatcher is set to [ignore](Self::with_ignore_unmapped) them.
+
+Only when the target changes is it returned in the event,
+so the code blocks in the same language are put in the same file in order.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::dispatch::{Event, DispatchErrless, ByLang};
+use std::path::Path;
+
+let mut disp = ByLang::new();
+disp.with("toml", Path::new("Cargo.toml"))
+    .with("rust", Path::new("src/lib.rs"))
+    .with_ignore_unmapped(true);
+let ctnt = [
+        CodeBlock::new("[package]\n", "toml", vec![]),
+        CodeBlock::new("cargo build\n", "sh", vec![]),
+        CodeBlock::new("fn a() {}\n", "rust", vec![]),
+        CodeBlock::new("fn b() {}\n", "rust", vec![]),
+    ]
+    .into_iter();
+assert_eq!(
+    disp.dispatch(ctnt).collect::<Vec<_>>(),
+    [
+        Event::new_some(Path::new("Cargo.toml"), CodeBlock::new("[package]\n", "toml", vec![])),
+        Event::new_some(Path::new("src/lib.rs"), CodeBlock::new("fn a() {}\n", "rust", vec![])),
+        Event::new_none(CodeBlock::new("fn b() {}\n", "rust", vec![])),
+    ]
+);
+```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ByLang<'t> {
+    /// The map from languages to targets.
+    table: HashMap<Cow<'t, str>, Cow<'t, Path>>,
+    /// The target of the languages absent in the table.
+    fallback: Option<Cow<'t, Path>>,
+    /// Whether to drop the code blocks in the languages absent in the table.
+    ignore_unmapped: bool,
+}
+
+impl<'t> ByLang<'t> {
+    /// Create a dispatcher with an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map the language to the target and return self.
+    pub fn with(&mut self, lang: impl Into<Cow<'t, str>>, target: impl Into<Cow<'t, Path>>) -> &mut Self {
+        self.table.insert(lang.into(), target.into());
+        self
+    }
+
+    /// Set the target of the languages absent in the table and return self.
+    pub fn with_fallback(&mut self, target: Option<Cow<'t, Path>>) -> &mut Self {
+        self.fallback = target;
+        self
+    }
+
+    /// Set whether to drop the code blocks in the languages absent in the table and return self.
+    ///
+    /// This has no effect if the fallback target is given.
+    pub fn with_ignore_unmapped(&mut self, ignore: bool) -> &mut Self {
+        self.ignore_unmapped = ignore;
+        self
+    }
+}
+
+/**
+The iterator returned by [`ByLang::dispatch`].
+
+See [the document of `Dispatch`](Dispatch) for more.
+*/
+#[derive(Debug, Clone)]
+pub struct Iter<'a, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    /// The dispatcher.
+    disp: &'a ByLang<'a>,
+    /// The target of the last code block.
+    last: Option<&'a Path>,
+    /// The underlying code block iterator.
+    iter: It,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for Iter<'a, It, E>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    type Item = Result<Event<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let blk = match self.iter.next()? {
+                Ok(blk) => blk,
+                Err(e) => return Some(Err(e)),
+            };
+            let target = match self.disp.table.get(blk.lang.as_ref()).or(self.disp.fallback.as_ref()) {
+                Some(x) => Some(x.as_ref()),
+                None if self.disp.ignore_unmapped => continue,
+                None => None,
+            };
+            let target = match target {
+                Some(x) if self.last != Some(x) => {
+                    self.last = Some(x);
+                    Some(x)
+                }
+                _ => None,
+            };
+            return Some(Ok(Event::new(target, blk)));
+        }
+    }
+}
+
+impl TargetOf for ByLang<'_> {
+    fn target_of<'a>(&'a self, blk: &CodeBlock<'a>) -> Option<Cow<'a, Path>> {
+        let target = self.table.get(blk.lang.as_ref()).or(self.fallback.as_ref())?;
+        Some(Cow::Borrowed(target))
+    }
+}
+
+impl Dispatch for ByLang<'_> {
+    type Output<'a, It, E: std::error::Error> = Iter<'a, It, E>
+    where
+        Self: 'a,
+        It: Iterator<Item = Result<CodeBlock<'a>, E>>;
+
+    fn dispatch<'a, It, E: std::error::Error>(&'a self, iter: It) -> Self::Output<'a, It, E>
+    where
+        It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+    {
+        Iter {
+            disp: self,
+            last: None,
+            iter,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::ByLang;
+    use crate::codeblock::CodeBlock;
+    use crate::dispatch::{DispatchErrless, Event};
+
+    #[test]
+    fn fallback() {
+        let ctnt = [
+            CodeBlock::new("1", "c", vec![]),
+            CodeBlock::new("2", "sh", vec![]),
+            CodeBlock::new("3", "c", vec![]),
+            CodeBlock::new("4", "h", vec![]),
+        ];
+        let mut disp = ByLang::new();
+        disp.with("c", Path::new("a.c")).with("h", Path::new("a.h"));
+        assert_eq!(
+            disp.dispatch(ctnt.clone().into_iter()).collect::<Vec<_>>(),
+            [
+                Event::new_some(Path::new("a.c"), CodeBlock::new("1", "c", vec![])),
+                Event::new_none(CodeBlock::new("2", "sh", vec![])),
+                Event::new_none(CodeBlock::new("3", "c", vec![])),
+                Event::new_some(Path::new("a.h"), CodeBlock::new("4", "h", vec![])),
+            ]
+        );
+        disp.with_fallback(Some(Path::new("README").into()));
+        assert_eq!(
+            disp.dispatch(ctnt.into_iter()).collect::<Vec<_>>(),
+            [
+                Event::new_some(Path::new("a.c"), CodeBlock::new("1", "c", vec![])),
+                Event::new_some(Path::new("README"), CodeBlock::new("2", "sh", vec![])),
+                Event::new_some(Path::new("a.c"), CodeBlock::new("3", "c", vec![])),
+                Event::new_some(Path::new("a.h"), CodeBlock::new("4", "h", vec![])),
+            ]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+/*!
+Skip the code blocks which shouldn't be written.
+*/
+
+
+
+use std::borrow::Cow;
+use std::iter::Map;
+use std::path::Path;
+
+use super::Event;
+use crate::codeblock::CodeBlock;
+
+
+
+/// The predicate matching the code blocks to skip.
+type Predicate<'f> = Box<dyn Fn(&CodeBlock) -> bool + 'f>;
+
+/**
+The rules deciding which code blocks to skip, e.g. examples and output listings.
+
+A code block is skipped if any rule matches it.
+Besides matching the languages and the attributes directly,
+the classes match both the attributes `("class", class)` and the bare flags `(class, "")`,
+so both `rust,ignore` and `{.rust .ignore}` in markdown are matched by the class `ignore`.
+
+The code block iterators can be filtered before dispatching with [`keeps`](Self::keeps) directly,
+and the event iterators can be filtered with [`FilterEvents`],
+where the target of a skipped event is moved to the next kept one, so the states are kept.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::dispatch::Filter;
+
+let mut filter = Filter::new();
+filter.skip_lang("console").skip_class("ignore");
+let ctnt = [
+    CodeBlock::new("$ ls\n", "console", vec![]),
+    CodeBlock::new("fn a() {}\n", "rust", vec![("class".into(), "ignore".into())]),
+    CodeBlock::new("fn b() {}\n", "rust", vec![]),
+];
+assert_eq!(
+    ctnt.into_iter().filter(|x| filter.keeps(x)).collect::<Vec<_>>(),
+    [CodeBlock::new("fn b() {}\n", "rust", vec![])]
+);
+```
+*/
+#[derive(Default)]
+pub struct Filter<'f> {
+    /// The rules matching the code blocks to skip.
+    rules: Vec<Predicate<'f>>,
+}
+
+impl std::fmt::Debug for Filter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Filter")
+            .field("rules", &self.rules.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'f> Filter<'f> {
+    /// Create a filter keeping all code blocks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip the code blocks in the language and return self.
+    pub fn skip_lang(&mut self, lang: &'f str) -> &mut Self {
+        self.skip_if(move |x| x.lang == lang)
+    }
+
+    /// Skip the code blocks with the attribute, whatever its value is, and return self.
+    pub fn skip_attr(&mut self, key: &'f str) -> &mut Self {
+        self.skip_if(move |x| x.attrs.iter().any(|(k, _)| k == key))
+    }
+
+    /// Skip the code blocks with the attribute of the value and return self.
+    pub fn skip_attr_value(&mut self, key: &'f str, value: &'f str) -> &mut Self {
+        self.skip_if(move |x| x.attrs.iter().any(|(k, v)| k == key && v == value))
+    }
+
+    /// Skip the code blocks with the class, see [the struct document](Self) for more, and return self.
+    pub fn skip_class(&mut self, class: &'f str) -> &mut Self {
+        self.skip_if(move |x| {
+            x.attrs
+                .iter()
+                .any(|(k, v)| (k == "class" && v == class) || (k == class && v.is_empty()))
+        })
+    }
+
+    /// Skip the code blocks for which the function returns `true`, and return self.
+    pub fn skip_if(&mut self, f: impl Fn(&CodeBlock) -> bool + 'f) -> &mut Self {
+        self.rules.push(Box::new(f));
+        self
+    }
+
+    /// Check whether the code block is kept, i.e. no rule matches it.
+    pub fn keeps(&self, blk: &CodeBlock) -> bool {
+        !self.rules.iter().any(|f| f(blk))
+    }
+}
+
+
+
+/**
+The iterator type returned by [`FilterEvents::filter_events`],
+see [the document of the trait](FilterEvents) for more.
+*/
+#[derive(Debug)]
+pub struct FilterIter<'a, 'f, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<Event<'a>, E>>,
+{
+    /// The underlying iterator.
+    iter: It,
+    /// The filter.
+    filter: &'f Filter<'f>,
+    /// The target of the last skipped event, which is not yet returned.
+    target: Option<Cow<'a, Path>>,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for FilterIter<'a, '_, It, E>
+where
+    It: Iterator<Item = Result<Event<'a>, E>>,
+{
+    type Item = Result<Event<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut event = match self.iter.next()? {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            if event.target.is_some() {
+                self.target = event.target.take();
+            }
+            if self.filter.keeps(&event.block) {
+                event.target = self.target.take();
+                return Some(Ok(event));
+            }
+        }
+    }
+}
+
+/**
+Skip the events whose code blocks are matched by the [filter](Filter).
+
+If a skipped event has a target, the target is moved to the next kept event
+(unless the kept event has its own target),
+so that the following code blocks are still dispatched to it.
+
+Example:
+
+```
+use scribere::dispatch::{Event, Filter, FilterEventsErrless};
+use scribere::CodeBlock;
+use std::path::Path;
+
+let ctnt = [
+    Event::new_some(Path::new("a"), CodeBlock::new("1", "text", vec![])),
+    Event::new_none(CodeBlock::new("2", "", vec![])),
+];
+let mut filter = Filter::new();
+filter.skip_lang("text");
+assert_eq!(
+    ctnt.into_iter().filter_events(&filter).collect::<Vec<_>>(),
+    [Event::new_some(Path::new("a"), CodeBlock::new("2", "", vec![]))]
+);
+```
+*/
+pub trait FilterEvents<'a, E: std::error::Error>: Iterator<Item = Result<Event<'a>, E>> + Sized {
+    /// Skip the events whose code blocks are matched by the filter.
+    ///
+    /// See [the trait document](Self) for more.
+    fn filter_events<'f>(self, filter: &'f Filter<'f>) -> FilterIter<'a, 'f, Self, E>;
+}
+
+impl<'a, E: std::error::Error, It: Iterator<Item = Result<Event<'a>, E>>> FilterEvents<'a, E> for It {
+    fn filter_events<'f>(self, filter: &'f Filter<'f>) -> FilterIter<'a, 'f, Self, E> {
+        FilterIter {
+            iter: self,
+            filter,
+            target: None,
+        }
+    }
+}
+
+
+
+/**
+The iterator type returned by [`FilterEventsErrless::filter_events`],
+see [the document of `FilterEvents`](FilterEvents) for more.
+*/
+#[derive(Debug)]
+//# The type is not intended for human to use its content ><
+#[allow(clippy::type_complexity)]
+pub struct FilterErrlessIter<'a, 'f, It: Iterator<Item = Event<'a>>>(
+    FilterIter<'a, 'f, Map<It, fn(Event<'a>) -> Result<Event<'a>, !>>, !>,
+);
+
+impl<'a, It: Iterator<Item = Event<'a>>> Iterator for FilterErrlessIter<'a, '_, It> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|x| x.expect("Never type should never occur"))
+    }
+}
+
+/**
+Skip the events in the errorless iterator whose code blocks are matched by the filter.
+
+See [the document of `FilterEvents`](FilterEvents) for more.
+*/
+pub trait FilterEventsErrless<'a>: Iterator<Item = Event<'a>> + Sized {
+    /// Skip the events whose code blocks are matched by the filter.
+    ///
+    /// See [the document of `FilterEvents`](FilterEvents) for more.
+    fn filter_events<'f>(self, filter: &'f Filter<'f>) -> FilterErrlessIter<'a, 'f, Self>;
+}
+
+impl<'a, It: Iterator<Item = Event<'a>>> FilterEventsErrless<'a> for It {
+    fn filter_events<'f>(self, filter: &'f Filter<'f>) -> FilterErrlessIter<'a, 'f, Self> {
+        FilterErrlessIter(FilterIter {
+            iter: self.map(Ok),
+            filter,
+            target: None,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Filter, FilterEvents};
+    use crate::codeblock::CodeBlock;
+    use crate::dispatch::Event;
+
+    #[test]
+    fn rules() {
+        let mut filter = Filter::new();
+        filter
+            .skip_attr("skip")
+            .skip_attr_value("output", "yes")
+            .skip_class("ignore")
+            .skip_if(|x| x.content.is_empty());
+        let keeps = |attrs: &[(&'static str, &'static str)]| {
+            let attrs = attrs.iter().map(|&(k, v)| (k.into(), v.into())).collect();
+            filter.keeps(&CodeBlock::new("x", "", attrs))
+        };
+        assert!(keeps(&[("output", "no"), ("class", "rust")]));
+        assert!(!keeps(&[("skip", "no")]));
+        assert!(!keeps(&[("output", "yes")]));
+        assert!(!keeps(&[("class", "ignore")]));
+        assert!(!keeps(&[("ignore", "")]));
+        assert!(keeps(&[("ignore", "no")]));
+        assert!(!filter.keeps(&CodeBlock::new("", "", vec![])));
+    }
+
+    #[test]
+    fn move_target() {
+        let ctnt = [
+            Ok(Event::new_some(Path::new("a"), CodeBlock::new("1", "text", vec![]))),
+            Ok(Event::new_some(Path::new("b"), CodeBlock::new("2", "text", vec![]))),
+            Err(std::fmt::Error),
+            Ok(Event::new_none(CodeBlock::new("3", "", vec![]))),
+            Ok(Event::new_some(Path::new("c"), CodeBlock::new("4", "text", vec![]))),
+            Ok(Event::new_some(Path::new("d"), CodeBlock::new("5", "", vec![]))),
+            Ok(Event::new_none(CodeBlock::new("6", "text", vec![]))),
+        ];
+        let mut filter = Filter::new();
+        filter.skip_lang("text");
+        assert_eq!(
+            ctnt.into_iter().filter_events(&filter).collect::<Vec<_>>(),
+            [
+                Err(std::fmt::Error),
+                Ok(Event::new_some(Path::new("b"), CodeBlock::new("3", "", vec![]))),
+                Ok(Event::new_some(Path::new("d"), CodeBlock::new("5", "", vec![]))),
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+/*!
+Dispatch the code blocks with the first dispatcher able to decide their targets.
+*/
+
+
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use super::{Dispatch, Event, TargetOf};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Dispatch each code block with the first dispatcher able to decide its target.
+
+The dispatchers are tried in order for each code block, with [`TargetOf::target_of`].
+If none of them can decide the target, the code block is dispatched to the same file as the last code block.
+Only when the target changes is it returned in the event,
+so that the states of the events are kept.
+
+Note that the dispatchers are evaluated per block,
+so a [`MonoFile`](super::MonoFile) decides the target of every code block here,
+and is a proper fallback at the end of the chain.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::dispatch::{ByAttr, ByLang, DispatchErrless, Event, FirstOf, MonoFile};
+use std::path::Path;
+
+let by_attr = ByAttr::new("file");
+let mut by_lang = ByLang::new();
+by_lang.with("toml", Path::new("Cargo.toml"));
+let misc = MonoFile::new(Path::new("misc.txt"));
+let mut disp = FirstOf::new();
+disp.with(&by_attr).with(&by_lang).with(&misc);
+let ctnt = [
+        CodeBlock::new("1", "toml", vec![]),
+        CodeBlock::new("2", "rust", vec![("file".into(), "a.rs".into())]),
+        CodeBlock::new("3", "sh", vec![]),
+        CodeBlock::new("4", "text", vec![]),
+    ]
+    .into_iter();
+assert_eq!(
+    disp.dispatch(ctnt).collect::<Vec<_>>(),
+    [
+        Event::new_some(Path::new("Cargo.toml"), CodeBlock::new("1", "toml", vec![])),
+        Event::new_some(
+            Path::new("a.rs"),
+            CodeBlock::new("2", "rust", vec![("file".into(), "a.rs".into())]),
+        ),
+        Event::new_some(Path::new("misc.txt"), CodeBlock::new("3", "sh", vec![])),
+        Event::new_none(CodeBlock::new("4", "text", vec![])),
+    ]
+);
+```
+*/
+#[derive(Clone, Default)]
+pub struct FirstOf<'d> {
+    /// The dispatchers to try in order.
+    disps: Vec<&'d dyn TargetOf>,
+}
+
+impl std::fmt::Debug for FirstOf<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FirstOf")
+            .field("disps", &self.disps.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'d> FirstOf<'d> {
+    /// Create a dispatcher without any dispatchers to try.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a dispatcher to try and return self.
+    pub fn with(&mut self, disp: &'d dyn TargetOf) -> &mut Self {
+        self.disps.push(disp);
+        self
+    }
+}
+
+impl TargetOf for FirstOf<'_> {
+    fn target_of<'a>(&'a self, blk: &CodeBlock<'a>) -> Option<Cow<'a, Path>> {
+        self.disps.iter().find_map(|x| x.target_of(blk))
+    }
+}
+
+/**
+The iterator returned by [`FirstOf::dispatch`].
+
+See [the document of `Dispatch`](Dispatch) for more.
+*/
+#[derive(Debug, Clone)]
+pub struct Iter<'a, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    /// The dispatcher.
+    disp: &'a FirstOf<'a>,
+    /// The target of the last code block.
+    last: Option<Cow<'a, Path>>,
+    /// The underlying code block iterator.
+    iter: It,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for Iter<'a, It, E>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    type Item = Result<Event<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let blk = match self.iter.next()? {
+            Ok(blk) => blk,
+            Err(e) => return Some(Err(e)),
+        };
+        let target = match self.disp.target_of(&blk) {
+            Some(x) if self.last.as_ref() != Some(&x) => {
+                self.last = Some(x.clone());
+                Some(x)
+            }
+            _ => None,
+        };
+        Some(Ok(Event::new(target, blk)))
+    }
+}
+
+impl Dispatch for FirstOf<'_> {
+    type Output<'a, It, E: std::error::Error> = Iter<'a, It, E>
+    where
+        Self: 'a,
+        It: Iterator<Item = Result<CodeBlock<'a>, E>>;
+
+    fn dispatch<'a, It, E: std::error::Error>(&'a self, iter: It) -> Self::Output<'a, It, E>
+    where
+        It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+    {
+        Iter {
+            disp: self,
+            last: None,
+            iter,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::FirstOf;
+    use crate::codeblock::CodeBlock;
+    use crate::dispatch::{ByAttr, DispatchErrless, Event, Missing, Template};
+
+    #[test]
+    fn chain() {
+        let tmpl = Template::new("{dir}/{name}");
+        let by_attr = ByAttr::new("name");
+        let mut inner = FirstOf::new();
+        inner.with(&tmpl);
+        let mut disp = FirstOf::new();
+        disp.with(&inner).with(&by_attr);
+        let ctnt = [
+            CodeBlock::new("1", "", vec![]),
+            CodeBlock::new("2", "", vec![("name".into(), "a".into())]),
+            CodeBlock::new("3", "", vec![("name".into(), "a".into()), ("dir".into(), "d".into())]),
+            CodeBlock::new("4", "", vec![("name".into(), "a".into())]),
+            CodeBlock::new("5", "", vec![]),
+        ];
+        assert_eq!(
+            disp.dispatch(ctnt.clone().into_iter()).collect::<Vec<_>>(),
+            [
+                Event::new_none(ctnt[0].clone()),
+                Event::new_some(Path::new("a"), ctnt[1].clone()),
+                Event::new_some(Path::new("d/a"), ctnt[2].clone()),
+                Event::new_some(Path::new("a"), ctnt[3].clone()),
+                Event::new_none(ctnt[4].clone()),
+            ]
+        );
+        // The missing mode only affects `Template::dispatch`
+        let mut tmpl = Template::new("{x}");
+        tmpl.with_missing(Missing::Error);
+        let mut disp = FirstOf::new();
+        disp.with(&tmpl);
+        assert_eq!(
+            disp.dispatch(ctnt[..1].iter().cloned()).collect::<Vec<_>>(),
+            [Event::new_none(ctnt[0].clone())]
+        );
+    }
+}
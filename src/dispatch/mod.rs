@@ -15,6 +15,8 @@ or the semantics of the event states is likely to be incorrect.
 In other words, only process the event iterator from the beginning,
 because any attempt of skipping the events without any proper attention to its states
 would likely lead to errors.
+To filter, sort or group the events, convert them into [`AbsEvent`]s with [`Absolutize`] first,
+and convert them back with [`Relativize`] afterwards.
 
 Due to the statefulness of the event iterator, there is a requirement
 that the first event returned by the `Iterator` created by [`Dispatch::dispatch`]
@@ -182,12 +184,39 @@ impl<T: Dispatch> DispatchErrless for T {
     }
 }
 
+/**
+Decide the target of each code block independently.
+
+Unlike [`Dispatch`], this is stateless: the target is decided only by the code block itself,
+and `None` means the implementor can't decide the target of the block,
+instead of the same target as the previous one.
+It's used to compose the dispatchers, see [`FirstOf`] for more.
+*/
+pub trait TargetOf {
+    /// Get the target of the code block, `None` if it can't be decided.
+    fn target_of<'a>(&'a self, blk: &CodeBlock<'a>) -> Option<Cow<'a, Path>>;
+}
+
 
 
 mod with_default;
 pub use with_default::{WithDefault, WithDefaultErrless};
+mod absolute;
+pub use absolute::{AbsEvent, AbsoluteError, Absolutize, AbsolutizeErrless, Relativize, RelativizeErrless};
+mod filter;
+pub use filter::{Filter, FilterEvents, FilterEventsErrless};
+mod rewrite;
+pub use rewrite::{Rewrite, RewriteTargets, RewriteTargetsErrless, Rule};
 
 mod monofile;
 pub use monofile::MonoFile;
 mod by_attr;
 pub use by_attr::ByAttr;
+mod by_attrs;
+pub use by_attrs::ByAttrs;
+mod by_lang;
+pub use by_lang::ByLang;
+mod template;
+pub use template::{Missing, Template, TemplateError};
+mod first_of;
+pub use first_of::FirstOf;
@@ -4,9 +4,10 @@ Dispatch all blocks into a single file.
 
 
 
+use std::borrow::Cow;
 use std::path::Path;
 
-use super::{Dispatch, Event};
+use super::{Dispatch, Event, TargetOf};
 use crate::codeblock::CodeBlock;
 
 
@@ -81,6 +82,12 @@ impl<'a, E: std::error::Error, It: Iterator<Item = Result<CodeBlock<'a>, E>>> It
     }
 }
 
+impl TargetOf for MonoFile<'_> {
+    fn target_of<'a>(&'a self, _: &CodeBlock<'a>) -> Option<Cow<'a, Path>> {
+        self.0.map(Cow::Borrowed)
+    }
+}
+
 impl Dispatch for MonoFile<'_> {
     type Output<'a, It, E: std::error::Error> = Iter<'a, It, E>
     where
@@ -0,0 +1,392 @@
+/*!
+Rewrite the dispatch targets of event iterators.
+*/
+
+
+
+use std::borrow::Cow;
+use std::iter::Map;
+use std::path::{Path, PathBuf};
+
+use super::Event;
+
+
+
+/**
+A rule rewriting a dispatch target, see [`Rewrite`] for more.
+*/
+pub enum Rule<'r> {
+    /// Replace the extension of the path, or remove it if the extension is empty.
+    Extension(Cow<'r, str>),
+    /// Strip the prefix of the path, the path is unchanged if it doesn't start with the prefix.
+    StripPrefix(Cow<'r, Path>),
+    /// Add the prefix to the path.
+    AddPrefix(Cow<'r, Path>),
+    /// Substitute the path matching the glob pattern with the replacement,
+    /// the path is unchanged if it doesn't match.
+    Glob {
+        /// The glob pattern.
+        pattern: Cow<'r, str>,
+        /// The replacement, in which `$1` to `$9` are the text matched by the wildcards.
+        replace: Cow<'r, str>,
+    },
+    /// Rewrite the path with a function.
+    Custom(Box<dyn Fn(&Path) -> PathBuf + 'r>),
+}
+
+impl std::fmt::Debug for Rule<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Extension(x) => f.debug_tuple("Extension").field(x).finish(),
+            Self::StripPrefix(x) => f.debug_tuple("StripPrefix").field(x).finish(),
+            Self::AddPrefix(x) => f.debug_tuple("AddPrefix").field(x).finish(),
+            Self::Glob { pattern, replace } => f
+                .debug_struct("Glob")
+                .field("pattern", pattern)
+                .field("replace", replace)
+                .finish(),
+            Self::Custom(_) => f.debug_tuple("Custom").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl Rule<'_> {
+    /// Apply the rule to the path.
+    pub fn apply(&self, path: &Path) -> PathBuf {
+        match self {
+            Self::Extension(ext) => path.with_extension(ext.as_ref()),
+            Self::StripPrefix(prefix) => path.strip_prefix(prefix).unwrap_or(path).to_path_buf(),
+            Self::AddPrefix(prefix) => prefix.join(path),
+            Self::Glob { pattern, replace } => path
+                .to_str()
+                .and_then(|x| glob_replace(pattern, replace, x))
+                .map_or_else(|| path.to_path_buf(), PathBuf::from),
+            Self::Custom(f) => f(path),
+        }
+    }
+}
+
+/**
+The rules rewriting dispatch targets, which are applied in order.
+
+Glob patterns are matched against the whole path, with `/` as the separator:
+`?` matches a character other than `/`, `*` matches any characters other than `/`,
+`**` matches any characters, and `**` followed by `/` matches any directories, including none.
+Each wildcard is captured in order, and can be referred in the replacement by `$1` to `$9`;
+`$$` is a literal `$`.
+
+Example:
+
+```
+use scribere::dispatch::Rewrite;
+use std::path::Path;
+
+let mut rules = Rewrite::new();
+rules
+    .with_strip_prefix(Path::new("docs"))
+    .with_glob("**.md", "src/$1")
+    .with_extension("rs");
+assert_eq!(rules.rewrite(Path::new("docs/a/b.md")), Path::new("src/a/b.rs"));
+assert_eq!(rules.rewrite(Path::new("docs/c.md")), Path::new("src/c.rs"));
+assert_eq!(rules.rewrite(Path::new("x.txt")), Path::new("x.rs"));
+```
+*/
+#[derive(Debug, Default)]
+pub struct Rewrite<'r> {
+    /// The rules to apply.
+    rules: Vec<Rule<'r>>,
+}
+
+impl<'r> Rewrite<'r> {
+    /// Create an empty set of rules, which keeps the targets as is.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule and return self.
+    pub fn with(&mut self, rule: Rule<'r>) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Add a rule replacing the extension and return self.
+    pub fn with_extension(&mut self, ext: impl Into<Cow<'r, str>>) -> &mut Self {
+        self.with(Rule::Extension(ext.into()))
+    }
+
+    /// Add a rule stripping the prefix and return self.
+    pub fn with_strip_prefix(&mut self, prefix: impl Into<Cow<'r, Path>>) -> &mut Self {
+        self.with(Rule::StripPrefix(prefix.into()))
+    }
+
+    /// Add a rule adding the prefix and return self.
+    pub fn with_prefix(&mut self, prefix: impl Into<Cow<'r, Path>>) -> &mut Self {
+        self.with(Rule::AddPrefix(prefix.into()))
+    }
+
+    /// Add a glob substitution rule and return self.
+    pub fn with_glob(&mut self, pattern: impl Into<Cow<'r, str>>, replace: impl Into<Cow<'r, str>>) -> &mut Self {
+        self.with(Rule::Glob {
+            pattern: pattern.into(),
+            replace: replace.into(),
+        })
+    }
+
+    /// Add a rule rewriting with the function and return self.
+    pub fn with_fn(&mut self, f: impl Fn(&Path) -> PathBuf + 'r) -> &mut Self {
+        self.with(Rule::Custom(Box::new(f)))
+    }
+
+    /// Rewrite the path with all rules.
+    pub fn rewrite(&self, path: &Path) -> PathBuf {
+        let mut res = path.to_path_buf();
+        for rule in &self.rules {
+            res = rule.apply(&res);
+        }
+        res
+    }
+}
+
+/// Match the whole text with the glob pattern,
+/// and substitute the captures into the replacement if matched.
+fn glob_replace(pattern: &str, replace: &str, text: &str) -> Option<String> {
+    let mut caps = vec![];
+    if !glob_match(pattern, text, &mut caps) {
+        return None;
+    }
+    let mut res = String::new();
+    let mut chars = replace.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('$', Some('$')) => {
+                chars.next();
+                res.push('$');
+            }
+            ('$', Some(&d @ '1'..='9')) => {
+                chars.next();
+                let idx = d as usize - '1' as usize;
+                res.push_str(caps.get(idx).copied().unwrap_or_default());
+            }
+            _ => res.push(c),
+        }
+    }
+    Some(res)
+}
+
+/// Match the whole text with the glob pattern, pushing the text matched by the wildcards into `caps`.
+fn glob_match<'t>(pattern: &str, text: &'t str, caps: &mut Vec<&'t str>) -> bool {
+    let (len, rest, any_dir) = if let Some(rest) = pattern.strip_prefix("**/") {
+        (3, rest, true)
+    } else if let Some(rest) = pattern.strip_prefix("**") {
+        (2, rest, false)
+    } else if let Some(rest) = pattern.strip_prefix('*') {
+        (1, rest, false)
+    } else if let Some(rest) = pattern.strip_prefix('?') {
+        let Some(c) = text.chars().next().filter(|&x| x != '/') else {
+            return false;
+        };
+        caps.push(&text[..c.len_utf8()]);
+        if glob_match(rest, &text[c.len_utf8()..], caps) {
+            return true;
+        }
+        caps.pop();
+        return false;
+    } else {
+        return match pattern.chars().next() {
+            None => text.is_empty(),
+            Some(c) => text.starts_with(c) && glob_match(&pattern[c.len_utf8()..], &text[c.len_utf8()..], caps),
+        };
+    };
+    // Try the shortest match of the wildcard first
+    let ends = text
+        .char_indices()
+        .map(|x| x.0)
+        .chain([text.len()])
+        .take_while(|&x| len != 1 || !text[..x].contains('/'))
+        .filter(|&x| !any_dir || x == 0 || text[..x].ends_with('/'));
+    for end in ends {
+        caps.push(&text[..end]);
+        if glob_match(rest, &text[end..], caps) {
+            return true;
+        }
+        caps.pop();
+    }
+    false
+}
+
+
+
+/**
+The iterator type returned by [`RewriteTargets::rewrite_targets`],
+see [the document of the trait](RewriteTargets) for more.
+*/
+#[derive(Debug)]
+pub struct RewriteIter<'a, 'r, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<Event<'a>, E>>,
+{
+    /// The underlying iterator.
+    iter: It,
+    /// The rules to rewrite the targets.
+    rules: &'r Rewrite<'r>,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for RewriteIter<'a, '_, It, E>
+where
+    It: Iterator<Item = Result<Event<'a>, E>>,
+{
+    type Item = Result<Event<'a>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut res = self.iter.next();
+        if let Some(Ok(Event {
+            target: Some(ref mut x),
+            ..
+        })) = res
+        {
+            *x = Cow::Owned(self.rules.rewrite(x));
+        }
+        res
+    }
+}
+
+/**
+Rewrite the dispatch targets of the event iterator with the [rules](Rewrite).
+
+Only the targets present are rewritten, so the states of the events are kept.
+
+Example:
+
+```
+use scribere::dispatch::{Event, Rewrite, RewriteTargetsErrless};
+use scribere::CodeBlock;
+use std::path::Path;
+
+let ctnt = [
+    Event::new_some(Path::new("src/a.md"), CodeBlock::new("1", "", vec![])),
+    Event::new_none(CodeBlock::new("2", "", vec![])),
+];
+let mut rules = Rewrite::new();
+rules.with_extension("rs");
+assert_eq!(
+    ctnt.into_iter().rewrite_targets(&rules).collect::<Vec<_>>(),
+    [
+        Event::new_some(Path::new("src/a.rs"), CodeBlock::new("1", "", vec![])),
+        Event::new_none(CodeBlock::new("2", "", vec![])),
+    ]
+);
+```
+*/
+pub trait RewriteTargets<'a, E: std::error::Error>: Iterator<Item = Result<Event<'a>, E>> + Sized {
+    /// Rewrite the dispatch targets of the event iterator.
+    ///
+    /// See [the trait document](Self) for more.
+    fn rewrite_targets<'r>(self, rules: &'r Rewrite<'r>) -> RewriteIter<'a, 'r, Self, E>;
+}
+
+impl<'a, E: std::error::Error, It: Iterator<Item = Result<Event<'a>, E>>> RewriteTargets<'a, E> for It {
+    fn rewrite_targets<'r>(self, rules: &'r Rewrite<'r>) -> RewriteIter<'a, 'r, Self, E> {
+        RewriteIter { iter: self, rules }
+    }
+}
+
+
+
+/**
+The iterator type returned by [`RewriteTargetsErrless::rewrite_targets`],
+see [the document of `RewriteTargets`](RewriteTargets) for more.
+*/
+#[derive(Debug)]
+//# The type is not intended for human to use its content ><
+#[allow(clippy::type_complexity)]
+pub struct RewriteErrlessIter<'a, 'r, It: Iterator<Item = Event<'a>>>(
+    RewriteIter<'a, 'r, Map<It, fn(Event<'a>) -> Result<Event<'a>, !>>, !>,
+);
+
+impl<'a, It: Iterator<Item = Event<'a>>> Iterator for RewriteErrlessIter<'a, '_, It> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|x| x.expect("Never type should never occur"))
+    }
+}
+
+/**
+Rewrite the dispatch targets of the errorless event iterator.
+
+See [the document of `RewriteTargets`](RewriteTargets) for more.
+*/
+pub trait RewriteTargetsErrless<'a>: Iterator<Item = Event<'a>> + Sized {
+    /// Rewrite the dispatch targets of the event iterator.
+    ///
+    /// See [the document of `RewriteTargets`](RewriteTargets) for more.
+    fn rewrite_targets<'r>(self, rules: &'r Rewrite<'r>) -> RewriteErrlessIter<'a, 'r, Self>;
+}
+
+impl<'a, It: Iterator<Item = Event<'a>>> RewriteTargetsErrless<'a> for It {
+    fn rewrite_targets<'r>(self, rules: &'r Rewrite<'r>) -> RewriteErrlessIter<'a, 'r, Self> {
+        RewriteErrlessIter(RewriteIter {
+            iter: self.map(Ok),
+            rules,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{glob_replace, Rewrite, RewriteTargets};
+    use crate::codeblock::CodeBlock;
+    use crate::dispatch::Event;
+
+    #[test]
+    fn glob() {
+        assert_eq!(glob_replace("*.md", "$1.rs", "a.md").unwrap(), "a.rs");
+        assert_eq!(glob_replace("*.md", "$1.rs", "a/b.md"), None);
+        assert_eq!(glob_replace("**.md", "$1.rs", "a/b.md").unwrap(), "a/b.rs");
+        assert_eq!(glob_replace("a/**/x", "[$1]", "a/x").unwrap(), "[]");
+        assert_eq!(glob_replace("a/**/x", "[$1]", "a/b/c/x").unwrap(), "[b/c/]");
+        assert_eq!(glob_replace("a/**/x", "[$1]", "a/bx"), None);
+        assert_eq!(glob_replace("?-*-?", "$3$2$1 $$1 $9", "a-bc-d").unwrap(), "dbca $1 ");
+        assert_eq!(glob_replace("??", "", "é"), None);
+    }
+
+    #[test]
+    fn rules() {
+        let mut rules = Rewrite::new();
+        rules
+            .with_strip_prefix(Path::new("docs"))
+            .with_extension("")
+            .with_fn(|x| x.join("mod.rs"))
+            .with_prefix(Path::new("src"));
+        assert_eq!(rules.rewrite(Path::new("docs/a.md")), Path::new("src/a/mod.rs"));
+        assert_eq!(rules.rewrite(Path::new("a")), Path::new("src/a/mod.rs"));
+        assert_eq!(
+            format!("{:?}", Rewrite::new().with_fn(|x| x.to_path_buf())),
+            "Rewrite { rules: [Custom(..)] }"
+        );
+    }
+
+    #[test]
+    fn errorful() {
+        let ctnt = [
+            Ok(Event::new_some(Path::new("a.md"), CodeBlock::new("1", "", vec![]))),
+            Err(std::fmt::Error),
+            Ok(Event::new_none(CodeBlock::new("2", "", vec![]))),
+        ];
+        let mut rules = Rewrite::new();
+        rules.with_glob("*.md", "src/$1.rs");
+        let res = ctnt.into_iter().rewrite_targets(&rules).collect::<Vec<_>>();
+        assert_eq!(
+            res,
+            [
+                Ok(Event::new_some(Path::new("src/a.rs"), CodeBlock::new("1", "", vec![]))),
+                Err(std::fmt::Error),
+                Ok(Event::new_none(CodeBlock::new("2", "", vec![]))),
+            ]
+        );
+    }
+}
@@ -0,0 +1,304 @@
+/*!
+Dispatch the code blocks to files with paths built from templates.
+*/
+
+
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::iter::Map;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use super::{Event, TargetOf};
+use crate::codeblock::CodeBlock;
+use crate::lang::EXTS;
+
+
+
+/**
+The error during dispatching with [`Template`].
+*/
+#[derive(Debug, Error)]
+pub enum TemplateError<B: std::error::Error> {
+    /// The placeholder is absent in the code block.
+    #[error("placeholder `{0}` is missing in the code block")]
+    Missing(String),
+    /// The error while iterating the code blocks.
+    #[error("iterating blocks error: {0}")]
+    BlockError(B),
+}
+
+/**
+What to do when a placeholder is absent in a code block, see [`Template`] for more.
+*/
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Missing {
+    /// Return [`TemplateError::Missing`].
+    #[default]
+    Error,
+    /// Drop the code block.
+    Skip,
+    /// Dispatch the code block to the same file as the last code block.
+    Inherit,
+}
+
+
+
+/**
+Dispatch the code blocks to files with paths built from a template.
+
+In the template, `{name}` is replaced with the value of the attribute `name` of the code block,
+except that `{lang}` is the [language](CodeBlock::lang) of the block,
+and `{lang_ext}` is the file extension of the language in the extension table;
+`{{` and `}}` are literal braces.
+The extension table contains [the common languages](crate::lang::ext) by default,
+and can be modified by [`with_ext`](Self::with_ext).
+
+When a placeholder is absent in a code block, e.g. the attribute is not given,
+or the language is empty or not in the extension table,
+the behavior is decided by [`Missing`], and it's an error by default.
+
+Only when the target changes is it returned in the event.
+
+Since missing placeholders may be errors, `Template` doesn't implement [`Dispatch`](super::Dispatch),
+but provides [`dispatch`](Self::dispatch) and [`dispatch_errless`](Self::dispatch_errless)
+returning iterators over [`TemplateError`]s instead.
+It implements [`TargetOf`] though, so it can be composed with [`FirstOf`](super::FirstOf),
+where a missing placeholder means the target can't be decided by it.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::dispatch::{Event, Missing, Template};
+use std::path::Path;
+
+let mut disp = Template::new("{module}/{name}.{lang_ext}");
+disp.with_missing(Missing::Inherit);
+let ctnt = [
+        CodeBlock::new("1", "rust", vec![("module".into(), "a".into()), ("name".into(), "b".into())]),
+        CodeBlock::new("2", "rust", vec![]),
+    ]
+    .into_iter();
+assert_eq!(
+    disp.dispatch_errless(ctnt).collect::<Result<Vec<_>, _>>().unwrap(),
+    [
+        Event::new_some(
+            Path::new("a/b.rs"),
+            CodeBlock::new("1", "rust", vec![("module".into(), "a".into()), ("name".into(), "b".into())]),
+        ),
+        Event::new_none(CodeBlock::new("2", "rust", vec![])),
+    ]
+);
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template<'t> {
+    /// The template of the paths.
+    format: Cow<'t, str>,
+    /// The map from languages to file extensions.
+    exts: HashMap<Cow<'t, str>, Cow<'t, str>>,
+    /// What to do when a placeholder is absent.
+    missing: Missing,
+}
+
+impl<'t> Template<'t> {
+    /// Create a dispatcher with the template, and the extensions of common languages.
+    pub fn new(format: impl Into<Cow<'t, str>>) -> Self {
+        let mut res = Self {
+            format: format.into(),
+            exts: HashMap::new(),
+            missing: Missing::default(),
+        };
+        for &(lang, ext) in EXTS {
+            res.with_ext(lang, ext);
+        }
+        res
+    }
+
+    /// Set the file extension of the language and return self.
+    pub fn with_ext(&mut self, lang: impl Into<Cow<'t, str>>, ext: impl Into<Cow<'t, str>>) -> &mut Self {
+        self.exts.insert(lang.into(), ext.into());
+        self
+    }
+
+    /// Set what to do when a placeholder is absent and return self.
+    pub fn with_missing(&mut self, missing: Missing) -> &mut Self {
+        self.missing = missing;
+        self
+    }
+
+    /// Build the target of the code block,
+    /// returning the name of the first absent placeholder if there is one.
+    pub fn render(&self, blk: &CodeBlock) -> Result<PathBuf, String> {
+        let mut res = String::new();
+        let mut rest = self.format.as_ref();
+        while let Some(pos) = rest.find(['{', '}']) {
+            res.push_str(&rest[..pos]);
+            let tail = &rest[pos..];
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                res.push_str(&tail[..1]);
+                rest = &tail[2..];
+                continue;
+            }
+            let Some(end) = tail.strip_prefix('{').and_then(|x| x.find('}')) else {
+                // Unpaired braces are kept as is
+                res.push_str(&tail[..1]);
+                rest = &tail[1..];
+                continue;
+            };
+            let name = &tail[1..end + 1];
+            let value = match name {
+                "lang" => Some(blk.lang.as_ref()).filter(|x| !x.is_empty()),
+                "lang_ext" => self.exts.get(blk.lang.as_ref()).map(|x| x.as_ref()),
+                _ => blk.attrs.iter().find(|x| x.0 == name).map(|x| x.1.as_ref()),
+            };
+            res.push_str(value.ok_or_else(|| name.to_string())?);
+            rest = &tail[end + 2..];
+        }
+        res.push_str(rest);
+        Ok(res.into())
+    }
+
+    /// Dispatch the code block iterator into an [`Event`] iterator.
+    ///
+    /// See [the struct document](Self) for more.
+    pub fn dispatch<'a, It, E: std::error::Error>(&'a self, iter: It) -> Iter<'a, It, E>
+    where
+        It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+    {
+        Iter {
+            disp: self,
+            last: None,
+            iter,
+        }
+    }
+
+    /// Dispatch the errorless code block iterator into an [`Event`] iterator.
+    ///
+    /// See [the struct document](Self) for more.
+    #[allow(clippy::type_complexity)]
+    pub fn dispatch_errless<'a, It>(
+        &'a self,
+        iter: It,
+    ) -> Iter<'a, Map<It, fn(CodeBlock<'a>) -> Result<CodeBlock<'a>, !>>, !>
+    where
+        It: Iterator<Item = CodeBlock<'a>>,
+    {
+        self.dispatch(iter.map(Ok as fn(_) -> _))
+    }
+}
+
+impl TargetOf for Template<'_> {
+    fn target_of<'a>(&'a self, blk: &CodeBlock<'a>) -> Option<Cow<'a, Path>> {
+        self.render(blk).ok().map(Cow::Owned)
+    }
+}
+
+/**
+The iterator returned by [`Template::dispatch`].
+
+See [the document of `Template`](Template) for more.
+*/
+#[derive(Debug, Clone)]
+pub struct Iter<'a, It, E: std::error::Error>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    /// The dispatcher.
+    disp: &'a Template<'a>,
+    /// The target of the last code block.
+    last: Option<PathBuf>,
+    /// The underlying code block iterator.
+    iter: It,
+}
+
+impl<'a, It, E: std::error::Error> Iterator for Iter<'a, It, E>
+where
+    It: Iterator<Item = Result<CodeBlock<'a>, E>>,
+{
+    type Item = Result<Event<'a>, TemplateError<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let blk = match self.iter.next()? {
+                Ok(blk) => blk,
+                Err(e) => return Some(Err(TemplateError::BlockError(e))),
+            };
+            let target = match self.disp.render(&blk) {
+                Ok(x) if self.last.as_deref() != Some(&x) => {
+                    self.last = Some(x.clone());
+                    Some(x)
+                }
+                Ok(_) => None,
+                Err(name) => match self.disp.missing {
+                    Missing::Error => return Some(Err(TemplateError::Missing(name))),
+                    Missing::Skip => continue,
+                    Missing::Inherit => None,
+                },
+            };
+            return Some(Ok(Event::new(target, blk)));
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Missing, Template, TemplateError};
+    use crate::codeblock::CodeBlock;
+    use crate::dispatch::Event;
+
+    #[test]
+    fn render() {
+        let blk = CodeBlock::new("", "python", vec![("name".into(), "a".into())]);
+        let mut disp = Template::new("{{{lang}}}/{name}.{lang_ext}{");
+        assert_eq!(disp.render(&blk).unwrap(), Path::new("{python}/a.py{"));
+        disp.with_ext("python", "py3");
+        assert_eq!(disp.render(&blk).unwrap(), Path::new("{python}/a.py3{"));
+        let blk = CodeBlock::new("", "", vec![]);
+        assert_eq!(disp.render(&blk).unwrap_err(), "lang");
+        assert_eq!(Template::new("{x}}").render(&blk).unwrap_err(), "x");
+    }
+
+    #[test]
+    fn missing() {
+        let ctnt = [
+            CodeBlock::new("1", "", vec![("a".into(), "x".into())]),
+            CodeBlock::new("2", "", vec![]),
+            CodeBlock::new("3", "", vec![("a".into(), "x".into())]),
+            CodeBlock::new("4", "", vec![("a".into(), "y".into())]),
+        ];
+        let mut disp = Template::new("{a}.txt");
+        let res = disp.dispatch_errless(ctnt.clone().into_iter()).collect::<Vec<_>>();
+        assert!(matches!(&res[1], Err(TemplateError::Missing(x)) if x == "a"));
+        disp.with_missing(Missing::Skip);
+        assert_eq!(
+            disp.dispatch_errless(ctnt.clone().into_iter())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            [
+                Event::new_some(Path::new("x.txt"), ctnt[0].clone()),
+                Event::new_none(ctnt[2].clone()),
+                Event::new_some(Path::new("y.txt"), ctnt[3].clone()),
+            ]
+        );
+        disp.with_missing(Missing::Inherit);
+        assert_eq!(
+            disp.dispatch_errless(ctnt.clone().into_iter())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            [
+                Event::new_some(Path::new("x.txt"), ctnt[0].clone()),
+                Event::new_none(ctnt[1].clone()),
+                Event::new_none(ctnt[2].clone()),
+                Event::new_some(Path::new("y.txt"), ctnt[3].clone()),
+            ]
+        );
+    }
+}
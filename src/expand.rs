@@ -0,0 +1,359 @@
+/*!
+Expand the noweb-style references to named chunks in the code blocks.
+
+This is the stage between reading and [dispatching](crate::dispatch),
+which makes real literate programming possible:
+a code block can be named by an attribute, and be referred by `<<name>>` in other blocks,
+then the reference would be replaced by the content of the named block.
+*/
+
+
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+The error during expanding.
+*/
+#[derive(Debug, Error)]
+pub enum ExpandError<B: std::error::Error> {
+    /// The referred chunk is not defined.
+    #[error("chunk `{0}` is referred but not defined")]
+    Undefined(String),
+    /// The chunks refer each other, the first chunk is referred by the last one again.
+    #[error("reference cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+    /// The error while iterating the code blocks.
+    #[error("iterating blocks error: {0}")]
+    BlockError(B),
+}
+
+
+
+/**
+Expand the references to named chunks in the code blocks.
+
+A chunk is the concatenation of all code blocks with the same name,
+which is given by the attribute specified in the constructor.
+A reference to a chunk is its name between the delimiters, which are `<<` and `>>` by default,
+and it's replaced by the content of the chunk, with the references in it expanded recursively.
+
+The text before the reference is kept before the first line of the chunk,
+and the following lines are indented to the same column as the reference in the output,
+which counts the last lines of the chunks expanded before it in the same line, so that the indentation of the reference is kept, e.g. with the chunk `a` being `b;\nc;\n`:
+
+```text
+    <<a>>    =>        b;
+                       c;
+```
+
+Since `<<` and `>>` are common operators in many languages, only references to defined chunks,
+or references taking up a whole line, are recognized as references,
+and other text like `a << b >> c` is left as is.
+The delimiters can also be escaped with the prefix given by [`with_escape`](Self::with_escape),
+e.g. `@<<` and `@>>` in noweb, which are never references, and are unescaped after expansion.
+
+The blocks with a referred name are the parts of other blocks,
+so they are removed from the result,
+and other blocks are kept in the original order, with their references expanded.
+A reference cycle is an [`ExpandError::Cycle`], even if no block outside the cycle refers to it.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::expand::Expander;
+
+let blocks = [
+    CodeBlock::new("fn main() {\n    <<body>>\n}\n", "rust", vec![]),
+    CodeBlock::new("let a = 1;\n", "rust", vec![("name".into(), "body".into())]),
+    CodeBlock::new("println!(\"{a}\");\n", "rust", vec![("name".into(), "body".into())]),
+];
+assert_eq!(
+    Expander::new("name").expand_errless(blocks).unwrap(),
+    [CodeBlock::new("fn main() {\n    let a = 1;\n    println!(\"{a}\");\n}\n", "rust", vec![])]
+);
+```
+*/
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Expander<'n> {
+    /// The name of the attribute indicating the name of the chunk.
+    name: &'n str,
+    /// The opening delimiter of references.
+    open: &'n str,
+    /// The closing delimiter of references.
+    close: &'n str,
+    /// The prefix escaping the delimiters.
+    escape: Option<&'n str>,
+}
+
+impl<'n> Expander<'n> {
+    /// Create an expander with the name of the attribute naming the chunks.
+    pub fn new(name: &'n str) -> Self {
+        Self {
+            name,
+            open: "<<",
+            close: ">>",
+            escape: None,
+        }
+    }
+
+    /// Set the delimiters of references and return self.
+    pub fn with_delims(&mut self, open: &'n str, close: &'n str) -> &mut Self {
+        self.open = open;
+        self.close = close;
+        self
+    }
+
+    /// Set the prefix escaping the delimiters, `None` for no escapes, and return self.
+    pub fn with_escape(&mut self, escape: Option<&'n str>) -> &mut Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Expand the references in all code blocks in the iterator.
+    ///
+    /// See [the struct document](Self) for more.
+    pub fn expand<'a, E: std::error::Error>(
+        &self,
+        it: impl IntoIterator<Item = Result<CodeBlock<'a>, E>>,
+    ) -> Result<Vec<CodeBlock<'a>>, ExpandError<E>> {
+        let blocks = it
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ExpandError::BlockError)?;
+        let mut chunks = HashMap::<&str, Vec<&str>>::new();
+        for blk in &blocks {
+            if let Some(name) = self.name_of(blk) {
+                chunks.entry(name).or_default().push(&blk.content);
+            }
+        }
+        let mut referred = HashSet::new();
+        for blk in &blocks {
+            for line in blk.content.lines() {
+                let mut rest = line;
+                while let Some((_, name, post)) = self.find_ref(rest, &chunks) {
+                    referred.insert(name.to_string());
+                    rest = post;
+                }
+            }
+        }
+        let mut res = vec![];
+        for blk in &blocks {
+            if matches!(self.name_of(blk), Some(x) if referred.contains(x)) {
+                continue;
+            }
+            let mut stack = vec![];
+            let mut blk = blk.clone();
+            blk.content = self.unescape(self.expand_text(&blk.content, &chunks, &mut stack)?);
+            res.push(blk);
+        }
+        // The cycles unreachable from the blocks kept above, e.g. a chunk referring itself only
+        for blk in &blocks {
+            if let Some(name) = self.name_of(blk).filter(|x| referred.contains(*x)) {
+                self.expand_text::<E>(&blk.content, &chunks, &mut vec![name.to_string()])?;
+            }
+        }
+        Ok(res)
+    }
+
+    /// Expand the references in all code blocks in the errorless iterator.
+    ///
+    /// See [the struct document](Self) for more.
+    pub fn expand_errless<'a>(
+        &self,
+        it: impl IntoIterator<Item = CodeBlock<'a>>,
+    ) -> Result<Vec<CodeBlock<'a>>, ExpandError<!>> {
+        self.expand(it.into_iter().map(Ok))
+    }
+
+    /// Get the chunk name of the block.
+    fn name_of<'b>(&self, blk: &'b CodeBlock) -> Option<&'b str> {
+        blk.attrs.iter().find(|x| x.0 == self.name).map(|x| x.1.as_ref())
+    }
+
+    /// Find the first reference in the line, and split the line into the text before it,
+    /// the name of the chunk, and the text after it.
+    fn find_ref<'l>(&self, line: &'l str, chunks: &HashMap<&str, Vec<&str>>) -> Option<(&'l str, &'l str, &'l str)> {
+        let mut from = 0;
+        while let Some(start) = line[from..].find(self.open).map(|x| x + from) {
+            if self.escape.is_some_and(|x| line[..start].ends_with(x)) {
+                from = start + self.open.len();
+                continue;
+            }
+            let after = &line[start + self.open.len()..];
+            let end = after.find(self.close)?;
+            let name = after[..end].trim();
+            let (pre, post) = (&line[..start], &after[end + self.close.len()..]);
+            let whole_line = pre.trim().is_empty() && post.trim().is_empty();
+            if !name.is_empty() && (whole_line || chunks.contains_key(name)) {
+                return Some((pre, name, post));
+            }
+            from = start + self.open.len();
+        }
+        None
+    }
+
+    /// Unescape the escaped delimiters in the expanded text.
+    fn unescape<'a>(&self, text: Cow<'a, str>) -> Cow<'a, str> {
+        let Some(escape) = self.escape else {
+            return text;
+        };
+        let (open, close) = (format!("{escape}{}", self.open), format!("{escape}{}", self.close));
+        if !text.contains(&open) && !text.contains(&close) {
+            return text;
+        }
+        text.replace(&open, self.open).replace(&close, self.close).into()
+    }
+
+    /// Expand the references in the text, `stack` is the chunks being expanded.
+    fn expand_text<'a, E: std::error::Error>(
+        &self,
+        text: &Cow<'a, str>,
+        chunks: &HashMap<&str, Vec<&str>>,
+        stack: &mut Vec<String>,
+    ) -> Result<Cow<'a, str>, ExpandError<E>> {
+        if !text.lines().any(|x| self.find_ref(x, chunks).is_some()) {
+            return Ok(text.clone());
+        }
+        let mut res = String::new();
+        for line in text.split_inclusive('\n') {
+            let mut rest = line;
+            while let Some((pre, name, post)) = self.find_ref(rest, chunks) {
+                if let Some(pos) = stack.iter().position(|x| x == name) {
+                    let mut cycle = stack[pos..].to_vec();
+                    cycle.push(name.to_string());
+                    return Err(ExpandError::Cycle(cycle));
+                }
+                let defs = chunks
+                    .get(name)
+                    .ok_or_else(|| ExpandError::Undefined(name.to_string()))?;
+                stack.push(name.to_string());
+                let mut chunk = String::new();
+                for def in defs {
+                    chunk.push_str(&self.expand_text(&Cow::Borrowed(*def), chunks, stack)?);
+                }
+                stack.pop();
+                res.push_str(pre);
+                // Indent by the current column, which includes the chunks spliced into the line before
+                let indent = res[res.rfind('\n').map_or(0, |x| x + 1)..]
+                    .chars()
+                    .map(|x| if x.is_whitespace() { x } else { ' ' })
+                    .collect::<String>();
+                for (i, l) in chunk.strip_suffix('\n').unwrap_or(&chunk).split('\n').enumerate() {
+                    if i != 0 {
+                        res.push('\n');
+                        if !l.is_empty() {
+                            res.push_str(&indent);
+                        }
+                    }
+                    res.push_str(l);
+                }
+                rest = post;
+            }
+            res.push_str(rest);
+        }
+        Ok(res.into())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpandError, Expander};
+    use crate::codeblock::CodeBlock;
+
+    /// Construct a named code block.
+    fn named<'a>(name: &'a str, ctnt: &'a str) -> CodeBlock<'a> {
+        CodeBlock::new(ctnt, "", vec![("name".into(), name.into())])
+    }
+
+    #[test]
+    fn recursive() {
+        let blocks = [
+            named("main", "int main() {\n    <<body>>\n    return <<ret>>;\n}\n"),
+            named("body", "if (a << 1 >> 2)\n    <<stmt>>\n\nputs(\"b\");\n"),
+            named("stmt", "puts(\"a\");\n"),
+            named("ret", "0\n"),
+            CodeBlock::new("cout << a >> b;\n", "", vec![]),
+        ];
+        assert_eq!(
+            Expander::new("name").expand_errless(blocks).unwrap(),
+            [
+                named(
+                    "main",
+                    "int main() {\n    if (a << 1 >> 2)\n        puts(\"a\");\n\n    puts(\"b\");\n    return 0;\n}\n"
+                ),
+                CodeBlock::new("cout << a >> b;\n", "", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn same_line() {
+        let blocks = [
+            named("main", "  <<a>> <<b>>;\n"),
+            named("a", "x\nyy\n"),
+            named("b", "1\n2\n"),
+        ];
+        assert_eq!(
+            Expander::new("name").expand_errless(blocks).unwrap(),
+            [named("main", "  x\n  yy 1\n     2;\n")]
+        );
+    }
+
+    #[test]
+    fn delims() {
+        let blocks = [named("a", "x = [[b]] + [[b]]\n"), named("b", "1\n")];
+        assert_eq!(
+            Expander::new("name")
+                .with_delims("[[", "]]")
+                .expand_errless(blocks)
+                .unwrap(),
+            [named("a", "x = 1 + 1\n")]
+        );
+    }
+
+    #[test]
+    fn escape() {
+        let blocks = [named("a", "<<b>> @<<b@>>\n"), named("b", "1\n")];
+        assert_eq!(
+            Expander::new("name")
+                .with_escape(Some("@"))
+                .expand_errless(blocks)
+                .unwrap(),
+            [named("a", "1 <<b>>\n")]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let blocks = [named("a", "<<b>>\n"), named("b", "<<c>>\n"), named("c", "<<b>>\n")];
+        assert!(matches!(
+            Expander::new("name").expand_errless(blocks),
+            Err(ExpandError::Cycle(x)) if x == ["b", "c", "b"]
+        ));
+        let blocks = [named("a", "<<a>>\n")];
+        assert!(matches!(
+            Expander::new("name").expand_errless(blocks),
+            Err(ExpandError::Cycle(x)) if x == ["a", "a"]
+        ));
+        let blocks = [named("a", "<<b>>\n"), named("b", "<<a>>\n")];
+        assert!(matches!(
+            Expander::new("name").expand_errless(blocks),
+            Err(ExpandError::Cycle(x)) if x == ["a", "b", "a"]
+        ));
+        let blocks = [named("a", "  <<undefined>>\n")];
+        assert!(matches!(
+            Expander::new("name").expand_errless(blocks),
+            Err(ExpandError::Undefined(x)) if x == "undefined"
+        ));
+    }
+}
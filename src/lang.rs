@@ -0,0 +1,55 @@
+/*!
+The information about the languages of code blocks.
+*/
+
+
+
+/// The file extensions of common languages, without the dots.
+pub(crate) const EXTS: &[(&str, &str)] = &[
+    ("c", "c"),
+    ("cpp", "cpp"),
+    ("c++", "cpp"),
+    ("csharp", "cs"),
+    ("elisp", "el"),
+    ("emacs-lisp", "el"),
+    ("go", "go"),
+    ("haskell", "hs"),
+    ("java", "java"),
+    ("javascript", "js"),
+    ("js", "js"),
+    ("json", "json"),
+    ("kotlin", "kt"),
+    ("lua", "lua"),
+    ("markdown", "md"),
+    ("ocaml", "ml"),
+    ("perl", "pl"),
+    ("python", "py"),
+    ("py", "py"),
+    ("ruby", "rb"),
+    ("rust", "rs"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("shell", "sh"),
+    ("toml", "toml"),
+    ("typescript", "ts"),
+    ("yaml", "yaml"),
+];
+
+
+
+/**
+Get the file extension of the language, without the dot, `None` if the language is not common.
+
+Example:
+
+```
+use scribere::lang::ext;
+
+assert_eq!(ext("rust"), Some("rs"));
+assert_eq!(ext("emacs-lisp"), Some("el"));
+assert_eq!(ext("zig"), None);
+```
+*/
+pub fn ext(lang: &str) -> Option<&'static str> {
+    EXTS.iter().find(|x| x.0 == lang).map(|x| x.1)
+}
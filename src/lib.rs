@@ -17,11 +17,15 @@
 
 
 mod codeblock;
-pub use codeblock::CodeBlock;
+pub use codeblock::{CodeBlock, Span};
 
 pub mod directory;
 pub mod dispatch;
+pub mod expand;
+pub mod lang;
+pub mod line_directive;
 pub mod read;
+pub mod source_map;
 
 pub mod write_blocks;
 #[doc(inline)]
@@ -0,0 +1,137 @@
+/*!
+Line directives mapping the lines in the output back to the source document.
+
+Many languages, e.g. C and Perl, support directives like `#line 42 "doc.md"`,
+which tell the compiler where the following lines are from,
+so that the diagnostics would point to the source document instead of the output file.
+*/
+
+
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+The table of line directives of languages, keyed by [`CodeBlock::lang`].
+
+A directive is a format string, in which `{line}` is replaced by the line of the first line of the block
+and `{file}` is replaced by the path to the source document,
+with `\` and `"` escaped by `\` so that it can be put in a string literal.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::line_directive::LineDirectives;
+use scribere::read::LineIndex;
+use std::path::Path;
+
+let mut blk = CodeBlock::new("int a;\n", "c", vec![]);
+blk.with_span(Some(LineIndex::new("\n\nint a;\n").span(Some(Path::new("doc.md").into()), 2..9)));
+let mut table = LineDirectives::new();
+assert_eq!(table.directive(&blk).unwrap(), "#line 3 \"doc.md\"");
+table.with("c", "// {file}:{line}");
+assert_eq!(table.directive(&blk).unwrap(), "// doc.md:3");
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDirectives<'t> {
+    /// The map from languages to directive formats.
+    table: HashMap<Cow<'t, str>, Cow<'t, str>>,
+}
+
+impl<'t> LineDirectives<'t> {
+    /// Create a table with the directives of common languages.
+    pub fn new() -> Self {
+        let mut res = Self::empty();
+        for lang in [
+            "c", "h", "cpp", "c++", "cc", "cxx", "hpp", "objc", "cuda", "cs", "csharp", "d", "lex", "yacc",
+        ] {
+            res.with(lang, "#line {line} \"{file}\"");
+        }
+        res.with("perl", "# line {line} \"{file}\"")
+            .with("haskell", "{-# LINE {line} \"{file}\" #-}")
+            .with("go", "//line {file}:{line}")
+            .with("ocaml", "# {line} \"{file}\"");
+        res
+    }
+
+    /// Create an empty table.
+    pub fn empty() -> Self {
+        Self { table: HashMap::new() }
+    }
+
+    /// Set the directive format of the language and return self.
+    pub fn with(&mut self, lang: impl Into<Cow<'t, str>>, format: impl Into<Cow<'t, str>>) -> &mut Self {
+        self.table.insert(lang.into(), format.into());
+        self
+    }
+
+    /// Remove the directive of the language and return self.
+    pub fn without(&mut self, lang: &str) -> &mut Self {
+        self.table.remove(lang);
+        self
+    }
+
+    /// Get the directive for the code block, without the trailing line break.
+    ///
+    /// Returns `None` if the language has no directive, or the path to the source of the block is unknown.
+    pub fn directive(&self, blk: &CodeBlock) -> Option<String> {
+        let span = blk.span.as_ref()?;
+        self.directive_at(&blk.lang, span.line, span.path.as_ref()?)
+    }
+
+    /// Get the directive of the language for the line in the file, without the trailing line break.
+    ///
+    /// Returns `None` if the language has no directive.
+    pub fn directive_at(&self, lang: &str, line: usize, file: &Path) -> Option<String> {
+        let format = self.table.get(lang)?;
+        let file = file.to_string_lossy().replace('\\', "\\\\").replace('"', "\\\"");
+        Some(format.replace("{line}", &line.to_string()).replace("{file}", &file))
+    }
+}
+
+impl Default for LineDirectives<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::LineDirectives;
+    use crate::codeblock::{CodeBlock, Span};
+
+    #[test]
+    fn directive() {
+        let mut blk = CodeBlock::new("my $a;\n", "perl", vec![]);
+        let table = LineDirectives::new();
+        assert_eq!(table.directive(&blk), None);
+        blk.with_span(Some(Span {
+            path: None,
+            start: 0,
+            end: 7,
+            line: 1,
+            column: 1,
+        }));
+        assert_eq!(table.directive(&blk), None);
+        blk.span
+            .as_mut()
+            .unwrap()
+            .with_path(Some(Path::new("a \"b\".md").into()));
+        assert_eq!(table.directive(&blk).unwrap(), "# line 1 \"a \\\"b\\\".md\"");
+        blk.with_lang("rust".into());
+        assert_eq!(table.directive(&blk), None);
+        assert_eq!(table.directive_at("go", 3, Path::new("a.go")).unwrap(), "//line a.go:3");
+        assert_eq!(table.directive_at("rust", 3, Path::new("a.rs")), None);
+    }
+}
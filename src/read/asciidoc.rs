@@ -0,0 +1,300 @@
+/*!
+Read the listing and literal blocks in [AsciiDoc] documents.
+
+[AsciiDoc]: https://asciidoc.org/
+*/
+
+
+
+use std::borrow::Cow;
+
+use super::util::{next_line, Text};
+use super::{Read, ReadOut, SourceCode};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Read the listing blocks delimited by `----` and the literal blocks delimited by `....` in AsciiDoc documents.
+
+The attribute lists in the block attribute lines like `[source,rust,file=src/main.rs]` before the block
+are the language and the attributes of the block:
+
+- if the first positional attribute, i.e. the style, is `source` or empty,
+  the second one is the language;
+- the other positional attributes are the bare flags `(name, "")`, e.g. `linenums`;
+- the named attributes `key=value` or `key="some value"` are the attributes `(key, value)`,
+  except that the roles in `role=...` are the attributes `("class", role)`;
+- the shorthands `#id`, `.role` and `%option` in the style are the attributes
+  `("id", id)`, `("class", role)` and `(option, "")`.
+
+So `[source,rust,file=src/main.rs]` is a block in the language `rust` with the attribute `("file", "src/main.rs")`,
+and it can be dispatched with `ByAttr::new("file")` directly.
+Besides, the block anchor `[[id]]` is the attribute `("id", id)`,
+and the block title `.Title` is the attribute `("title", "Title")`.
+The attributes are not expanded, and the paragraphs in the `source` style without delimiters are not read.
+
+The content of a block is kept as is, and an unterminated block ends at the end of the document.
+Blocks in comment blocks delimited by `////` are ignored.
+The [`span`](CodeBlock::span) of a code block is the lines between the delimiters.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::Read;
+use scribere::read::asciidoc::Reader;
+
+let mut src = "\
+= Main
+
+.The entry
+[source,rust,file=src/main.rs]
+----
+fn main() {}
+----
+"
+.into();
+let blocks = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(blocks[0].content, "fn main() {}\n");
+assert_eq!(blocks[0].lang, "rust");
+assert_eq!(
+    blocks[0].attrs,
+    [("title".into(), "The entry".into()), ("file".into(), "src/main.rs".into())]
+);
+assert_eq!(blocks[0].span.as_ref().unwrap().line, 6);
+```
+*/
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reader;
+
+impl Reader {
+    /// Construct a new reader.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/**
+The output type of [`Reader::read`].
+*/
+#[derive(Debug, Clone)]
+pub struct ReaderOut<'a> {
+    /// The source being read.
+    text: Text<'a>,
+}
+
+impl<'a> Iterator for ReaderOut<'a> {
+    type Item = CodeBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut lang = "";
+        let mut attrs = vec![];
+        loop {
+            let line = next_line(&mut self.text.rest)?.trim_end();
+            if is_delimiter(line, '/') {
+                take_block(&mut self.text.rest, line);
+            } else if is_delimiter(line, '-') || is_delimiter(line, '.') {
+                let start = self.text.rest;
+                let content = take_block(&mut self.text.rest, line);
+                let offset = self.text.offset(start);
+                let mut blk = CodeBlock::new(content, lang, attrs);
+                blk.span = Some(self.text.span(offset..offset + content.len()));
+                return Some(blk);
+            } else if line.starts_with("//") {
+                // Line comments don't break the block metadata
+                continue;
+            } else if let Some(anchor) = line.strip_prefix("[[").and_then(|x| x.strip_suffix("]]")) {
+                let id = anchor.split_once(',').map_or(anchor, |x| x.0).trim();
+                attrs.push(("id".into(), id.into()));
+                continue;
+            } else if let Some(list) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                parse_attrs(list, &mut lang, &mut attrs);
+                continue;
+            } else if let Some(title) = line
+                .strip_prefix('.')
+                .filter(|x| !x.starts_with(['.', ' ', '\t']) && !x.is_empty())
+            {
+                attrs.push(("title".into(), title.into()));
+                continue;
+            }
+            lang = "";
+            attrs.clear();
+        }
+    }
+}
+
+impl ReadOut for Reader {
+    type Output<'a> = ReaderOut<'a>;
+}
+
+impl Read for Reader {
+    type Error = std::io::Error;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        Ok(ReaderOut { text: Text::new(src)? })
+    }
+}
+
+/// Check whether the line is a block delimiter of at least four characters.
+fn is_delimiter(line: &str, ch: char) -> bool {
+    line.len() >= 4 && line.chars().all(|x| x == ch)
+}
+
+/// Take the content of the block until the closing delimiter, or the end of the source.
+fn take_block<'a>(rest: &mut &'a str, delim: &str) -> &'a str {
+    let start = *rest;
+    let mut len = 0;
+    while let Some(line) = next_line(rest) {
+        if line.trim_end() == delim {
+            break;
+        }
+        len += line.len();
+    }
+    &start[..len]
+}
+
+/// Split the attribute list by the commas outside the double quotes.
+fn split_list(list: &str) -> Vec<&str> {
+    let mut res = vec![];
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, ch) in list.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                res.push(list[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    res.push(list[start..].trim());
+    res
+}
+
+/// Remove the double quotes around the value, if any.
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|x| x.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Parse the attribute list of a block attribute line into the language and the attributes.
+fn parse_attrs<'a>(list: &'a str, lang: &mut &'a str, attrs: &mut Vec<(Cow<'a, str>, Cow<'a, str>)>) {
+    let mut source = false;
+    for (pos, item) in split_list(list)
+        .into_iter()
+        .filter(|x| x.starts_with('"') || !x.contains('='))
+        .enumerate()
+    {
+        let item = unquote(item);
+        if pos == 0 {
+            let end = item.find(['#', '.', '%']).unwrap_or(item.len());
+            source = matches!(&item[..end], "" | "source");
+            let mut rest = &item[end..];
+            while let Some(marker) = rest.chars().next() {
+                let end = rest[1..].find(['#', '.', '%']).map_or(rest.len(), |x| x + 1);
+                let value = &rest[1..end];
+                match marker {
+                    '#' => attrs.push(("id".into(), value.into())),
+                    '.' => attrs.push(("class".into(), value.into())),
+                    _ => attrs.push((value.into(), "".into())),
+                }
+                rest = &rest[end..];
+            }
+        } else if pos == 1 && source {
+            *lang = item;
+        } else if !item.is_empty() {
+            attrs.push((item.into(), "".into()));
+        }
+    }
+    for item in split_list(list) {
+        let Some((key, value)) = item.split_once('=').filter(|_| !item.starts_with('"')) else {
+            continue;
+        };
+        let (key, value) = (key.trim(), unquote(value.trim()));
+        if key == "role" {
+            attrs.extend(value.split_whitespace().map(|x| ("class".into(), x.into())));
+        } else {
+            attrs.push((key.into(), value.into()));
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::Reader;
+    use crate::codeblock::CodeBlock;
+    use crate::read::util::lines;
+    use crate::read::Read;
+
+    #[test]
+    fn attrs() {
+        let mut src = "\
+[[hello,Hello]]
+[source#main.a.b%linenums,c,linenums,file=\"main.c\", role=\"c d\",\"x=y\"]
+// comment
+-----
+int main() {}
+----
+-----
+
+[source,python]
+text
+....
+  literal
+....
+[,sh]
+....
+echo
+"
+        .into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [5, 12, 16]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(
+                    "int main() {}\n----\n",
+                    "c",
+                    vec![
+                        ("id".into(), "hello".into()),
+                        ("id".into(), "main".into()),
+                        ("class".into(), "a".into()),
+                        ("class".into(), "b".into()),
+                        ("linenums".into(), "".into()),
+                        ("linenums".into(), "".into()),
+                        ("x=y".into(), "".into()),
+                        ("file".into(), "main.c".into()),
+                        ("class".into(), "c".into()),
+                        ("class".into(), "d".into()),
+                    ]
+                ),
+                CodeBlock::new("  literal\n", "", vec![]),
+                CodeBlock::new("echo\n", "sh", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let mut src = "\
+////
+----
+ignored
+----
+////
+[listing,x]
+----
+----
+"
+        .into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [8]);
+        assert_eq!(res, [CodeBlock::new("", "", vec![("x".into(), "".into())])]);
+    }
+}
@@ -6,8 +6,11 @@ Read markdown with the [pulldown-cmark] library.
 
 
 
-use pulldown_cmark::{Event, Parser};
+use std::borrow::Cow;
 
+use pulldown_cmark::{CodeBlockKind, Event, OffsetIter, Parser, Tag};
+
+use super::util::Text;
 use super::{Read, ReadOut, SourceCode};
 use crate::codeblock::CodeBlock;
 
@@ -15,6 +18,9 @@ use crate::codeblock::CodeBlock;
 
 /**
 Read the source code and filter out the code blocks in it.
+
+The [`span`](CodeBlock::span) of the code blocks returned by the filter is
+the range of the event, if the filter leaves it `None`.
 */
 #[derive(Debug, Clone)]
 pub struct Reader<F: Clone + for<'a> FnMut(Event<'a>) -> Option<CodeBlock<'a>>> {
@@ -37,15 +43,20 @@ pub struct ReaderOut<'a, F: Clone + FnMut(Event<'a>) -> Option<CodeBlock<'a>>> {
     /// The filter to pick out the blocks.
     filter: F,
     /// The parser generating the blocks.
-    it: Parser<'a, 'a>,
+    it: OffsetIter<'a, 'a>,
+    /// The source being read.
+    text: Text<'a>,
 }
 
 impl<'a, F: Clone + FnMut(Event<'a>) -> Option<CodeBlock<'a>>> Iterator for ReaderOut<'a, F> {
     type Item = CodeBlock<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        for ev in self.it.by_ref() {
-            if let Some(blk) = (self.filter)(ev) {
+        for (ev, range) in self.it.by_ref() {
+            if let Some(mut blk) = (self.filter)(ev) {
+                if blk.span.is_none() {
+                    blk.span = Some(self.text.span(range));
+                }
                 return Some(blk);
             }
         }
@@ -61,19 +72,228 @@ impl<F: Clone + for<'a> FnMut(Event<'a>) -> Option<CodeBlock<'a>>> Read for Read
     type Error = std::io::Error;
 
     fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
-        src.to_code()?;
+        let text = Text::new(src)?;
         Ok(ReaderOut {
             filter: self.filter.clone(),
-            it: Parser::new(src.as_code().expect("`src` should be code after conversion")),
+            it: Parser::new(text.rest).into_offset_iter(),
+            text,
         })
     }
 }
 
 
 
+/**
+Read the fenced code blocks in the markdown.
+
+The text in a fenced block is joined into the content of the code block,
+and its info string is parsed with [`parse_info`] to get the language and the attributes.
+Indented code blocks are not fenced, so they would be ignored.
+The [`span`](CodeBlock::span) of a code block is the range of its content,
+or the start of the block if it's empty.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::Read;
+use scribere::read::cmark::FencedReader;
+
+let mut src = "```rust {file=src/main.rs .ignore}\nfn main() {}\n```\n".into();
+let blocks = FencedReader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(
+    blocks,
+    [CodeBlock::new("fn main() {}\n", "rust", vec![
+        ("file".into(), "src/main.rs".into()),
+        ("class".into(), "ignore".into()),
+    ])]
+);
+assert_eq!(blocks[0].span.as_ref().unwrap().line, 2);
+```
+*/
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FencedReader;
+
+impl FencedReader {
+    /// Construct a new reader.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/**
+The output type of [`FencedReader::read`].
+*/
+#[allow(missing_debug_implementations)] // `Parser` dont impl `Debug` >_<
+pub struct FencedReaderOut<'a> {
+    /// The parser generating the events.
+    it: OffsetIter<'a, 'a>,
+    /// The source being read.
+    text: Text<'a>,
+}
+
+impl<'a> Iterator for FencedReaderOut<'a> {
+    type Item = CodeBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (info, start) = self.it.find_map(|(ev, range)| match ev {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => Some((info, range.start)),
+            _ => None,
+        })?;
+        let mut content = Cow::Borrowed("");
+        let mut range = start..start;
+        for (ev, r) in self.it.by_ref() {
+            match ev {
+                Event::Text(s) if content.is_empty() => {
+                    content = s.into();
+                    range = r;
+                }
+                Event::Text(s) => {
+                    content.to_mut().push_str(&s);
+                    range.end = r.end;
+                }
+                Event::End(Tag::CodeBlock(_)) => break,
+                _ => (),
+            }
+        }
+        let (lang, attrs) = parse_info(info.into());
+        let mut blk = CodeBlock::new(content, lang, attrs);
+        blk.span = Some(self.text.span(range));
+        Some(blk)
+    }
+}
+
+impl ReadOut for FencedReader {
+    type Output<'a> = FencedReaderOut<'a>;
+}
+
+impl Read for FencedReader {
+    type Error = std::io::Error;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        let text = Text::new(src)?;
+        Ok(FencedReaderOut {
+            it: Parser::new(text.rest).into_offset_iter(),
+            text,
+        })
+    }
+}
+
+/**
+Parse the info string of a fenced code block into its language and attributes.
+
+The first word of the info string is the language,
+and the words following it are the attributes, optionally wrapped in braces:
+
+- `key=value` or `key="some value"` is the attribute `(key, value)`;
+- `#name` is the attribute `("id", name)`;
+- `.name` is the attribute `("class", name)`;
+- a bare `word` is the attribute `(word, "")`.
+
+For compatibility with rustdoc, the language may be followed by classes separated by commas,
+e.g. `rust,ignore` is the language `rust` with the attribute `("class", "ignore")`;
+and for compatibility with pandoc, when the info string starts with a brace,
+the first class would be the language.
+
+```
+use scribere::read::cmark::parse_info;
+
+let (lang, attrs) = parse_info("c {#main file=\"main.c\" .ignore}".into());
+assert_eq!(lang, "c");
+assert_eq!(
+    attrs,
+    [("id".into(), "main".into()), ("file".into(), "main.c".into()), ("class".into(), "ignore".into())]
+);
+```
+*/
+#[allow(clippy::type_complexity)]
+pub fn parse_info(info: Cow<'_, str>) -> (Cow<'_, str>, Vec<(Cow<'_, str>, Cow<'_, str>)>) {
+    match info {
+        Cow::Borrowed(info) => {
+            let (lang, attrs) = split_info(info);
+            (
+                lang.into(),
+                attrs.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+            )
+        }
+        Cow::Owned(info) => {
+            let (lang, attrs) = split_info(&info);
+            (
+                lang.to_string().into(),
+                attrs
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string().into(), v.to_string().into()))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Split the info string into slices, see [`parse_info`] for the syntax.
+fn split_info(info: &str) -> (&str, Vec<(&str, &str)>) {
+    let info = info.trim();
+    let (word, rest) = if info.starts_with('{') {
+        ("", info)
+    } else {
+        info.split_once(char::is_whitespace).unwrap_or((info, ""))
+    };
+    let mut classes = word.split(',');
+    let mut lang = classes.next().unwrap_or_default();
+    let mut attrs = classes
+        .filter(|x| !x.is_empty())
+        .map(|x| ("class", x))
+        .collect::<Vec<_>>();
+    let rest = rest.trim();
+    let rest = match rest.strip_prefix('{') {
+        Some(x) => x.strip_suffix('}').unwrap_or(x),
+        None => rest,
+    };
+    for tok in split_words(rest) {
+        attrs.push(if let Some(id) = tok.strip_prefix('#') {
+            ("id", id)
+        } else if let Some(class) = tok.strip_prefix('.') {
+            if lang.is_empty() {
+                lang = class;
+            }
+            ("class", class)
+        } else if let Some((k, v)) = tok.split_once('=') {
+            (k, v.strip_prefix('"').and_then(|x| x.strip_suffix('"')).unwrap_or(v))
+        } else {
+            (tok, "")
+        });
+    }
+    (lang, attrs)
+}
+
+/// Split the string at whitespaces, but not the ones quoted in `"`.
+fn split_words(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let mut quoted = false;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                !quoted && c.is_whitespace()
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        let (word, tail) = rest.split_at(end);
+        rest = tail;
+        Some(word)
+    })
+}
+
+
+
 #[cfg(test)]
 mod tests {
-    use super::Reader;
+    use super::{parse_info, FencedReader, Reader};
     use crate::codeblock::CodeBlock;
     use crate::read::Read;
 
@@ -84,6 +304,7 @@ mod tests {
                 lang: "".into(),
                 content: x.to_string().into(),
                 attrs: vec![],
+                span: None,
             }),
             _ => None,
         });
@@ -97,6 +318,7 @@ mod tests {
                 lang: "".into(),
                 content: "print(world)".into(),
                 attrs: vec![],
+                span: None,
             }]
         );
         assert_eq!(
@@ -105,7 +327,67 @@ mod tests {
                 lang: "".into(),
                 content: "CodeBlocks".into(),
                 attrs: vec![],
+                span: None,
             }]
         );
     }
+
+    #[test]
+    fn fenced() {
+        let mut src = "\
+text
+```rust,ignore
+fn a() {}
+
+fn b() {}
+```
+
+    indented
+
+```{.python #main file=\"a b.py\"}
+print()
+```
+
+~~~
+~~~
+"
+        .into();
+        let res = FencedReader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(
+            res.iter()
+                .map(|x| x.span.as_ref().map(|x| (x.line, x.column, x.range())))
+                .collect::<Vec<_>>(),
+            [Some((3, 1, 20..41)), Some((11, 1, 93..101)), Some((14, 1, 106..106))]
+        );
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(
+                    "fn a() {}\n\nfn b() {}\n",
+                    "rust",
+                    vec![("class".into(), "ignore".into())]
+                ),
+                CodeBlock::new(
+                    "print()\n",
+                    "python",
+                    vec![
+                        ("class".into(), "python".into()),
+                        ("id".into(), "main".into()),
+                        ("file".into(), "a b.py".into()),
+                    ]
+                ),
+                CodeBlock::new("", "", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn info_string() {
+        let (lang, attrs) = parse_info(String::from("toml  flag   key=value").into());
+        assert_eq!(lang, "toml");
+        assert_eq!(attrs, [("flag".into(), "".into()), ("key".into(), "value".into())]);
+        let (lang, attrs) = parse_info("".into());
+        assert_eq!(lang, "");
+        assert_eq!(attrs, []);
+    }
 }
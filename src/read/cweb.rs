@@ -0,0 +1,395 @@
+/*!
+Read the code in [CWEB] and WEB files.
+
+[CWEB]: https://www-cs-faculty.stanford.edu/~knuth/cweb.html
+*/
+
+
+
+use super::util::Text;
+use super::{Read, ReadOut, SourceCode};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Read the code parts of the sections, i.e. the unnamed program code and the named modules, in CWEB and WEB files.
+
+The sections start with `@ ` or `@*`, and are numbered from 1 in order.
+The code part of a section starts with `@c` (or `@p`) for the unnamed program code,
+`@<Name@>=` for the named module, or `@(file@>=` for the code written to the output file,
+and ends at the start of the next section.
+Each code part is a code block with the attribute `("section", number)`,
+and the named modules have the attribute `("name", name)` before it,
+where the abbreviated names like `@<Print the...@>` are expanded to the full names,
+and the spaces in the names are normalized;
+while the output files have the attribute `("file", file)` before it,
+so they can be dispatched with `ByAttr::new("file")`.
+
+The content of a code block is what `ctangle` would see, with the control codes handled:
+
+- `@@` is unescaped to `@`;
+- the references to modules are kept as `@<Name@>`, with the names expanded and normalized like above,
+  so they can be expanded by [`Expander::new("name").with_delims("@<", "@>")`](crate::expand::Expander);
+- the verbatim `@=...@>` is replaced by the text in it;
+- the index entries, the comments and the TeX text like `@^...@>`, `@q...@>` and `@t...@>` are removed,
+  and so are the formatting codes like `@,`, `@/` and `@;`;
+- other control codes are kept as is.
+
+The definition parts like `@d` and the included files by `@i` are not read.
+The language of the code blocks is `c`, which can be changed by [`with_lang`](Self::with_lang), e.g. for WEB.
+The [`span`](CodeBlock::span) of a code block is the range of its code part.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::expand::Expander;
+use scribere::read::Read;
+use scribere::read::cweb::Reader;
+
+let mut src = "\
+\\def\\title{HELLO}
+@* Introduction. This program prints a greeting.
+@c
+int main() {
+    @<Print the greeting@>@;
+}
+
+@ The greeting contains an @@.
+@<Print the...@>=
+puts(\"hello@@example.com\");
+"
+.into();
+let blocks = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(blocks[0].content, "int main() {\n    @<Print the greeting@>\n}\n");
+assert_eq!(blocks[0].lang, "c");
+assert_eq!(blocks[0].attrs, [("section".into(), "1".into())]);
+assert_eq!(blocks[0].span.as_ref().unwrap().line, 4);
+assert_eq!(
+    blocks[1].attrs,
+    [("name".into(), "Print the greeting".into()), ("section".into(), "2".into())]
+);
+let mut expander = Expander::new("name");
+expander.with_delims("@<", "@>");
+assert_eq!(
+    expander.expand_errless(blocks).unwrap()[0].content,
+    "int main() {\n    puts(\"hello@example.com\");\n}\n"
+);
+```
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reader {
+    /// The language of the code.
+    lang: String,
+}
+
+impl Default for Reader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reader {
+    /// Construct a new reader for CWEB, with the language `c`.
+    pub fn new() -> Self {
+        Self { lang: "c".into() }
+    }
+
+    /// Set the language of the code and return self.
+    pub fn with_lang(&mut self, lang: impl Into<String>) -> &mut Self {
+        self.lang = lang.into();
+        self
+    }
+}
+
+/**
+The output type of [`Reader::read`].
+*/
+#[derive(Debug, Clone)]
+pub struct ReaderOut<'a> {
+    /// The source being read.
+    text: Text<'a>,
+    /// The language of the code.
+    lang: String,
+    /// The full names of the modules, normalized.
+    names: Vec<String>,
+    /// The number of the current section, 0 in limbo.
+    section: usize,
+}
+
+impl<'a> ReaderOut<'a> {
+    /// Take the code part of the current section from the remaining source,
+    /// with the attribute of the name or the file before the section number.
+    fn code(&mut self, head: Option<(&'static str, String)>) -> CodeBlock<'a> {
+        let line_end = self.text.rest.find('\n').map_or(self.text.rest.len(), |x| x + 1);
+        if self.text.rest[..line_end].trim().is_empty() {
+            self.text.rest = &self.text.rest[line_end..];
+        }
+        let src = self.text.rest;
+        let mut end = src.len();
+        let mut from = 0;
+        while let Some(pos) = src[from..].find('@') {
+            let pos = from + pos;
+            from = match src[pos + 1..].chars().next() {
+                None => break,
+                Some(' ' | '\t' | '\r' | '\n' | '*') => {
+                    end = pos;
+                    break;
+                }
+                Some(code @ ('<' | '(' | '=' | '^' | '.' | ':' | 't' | 'q')) => {
+                    src.len() - control_text(&src[pos + 1 + code.len_utf8()..]).1.len()
+                }
+                Some(code) => pos + 1 + code.len_utf8(),
+            };
+        }
+        self.text.rest = &src[end..];
+        let code = src[..end].trim_end();
+        let mut content = tangle(code, &self.names);
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        let mut attrs = vec![];
+        if let Some((key, value)) = head {
+            attrs.push((key.into(), value.into()));
+        }
+        attrs.push(("section".into(), self.section.to_string().into()));
+        let offset = self.text.offset(src);
+        let mut blk = CodeBlock::new(content, self.lang.clone(), attrs);
+        blk.span = Some(self.text.span(offset..offset + code.len()));
+        blk
+    }
+}
+
+impl<'a> Iterator for ReaderOut<'a> {
+    type Item = CodeBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let pos = self.text.rest.find('@')?;
+            let Some(code) = self.text.rest[pos + 1..].chars().next() else {
+                self.text.rest = "";
+                return None;
+            };
+            let tail = &self.text.rest[pos + 1 + code.len_utf8()..];
+            match code {
+                ' ' | '\t' | '\r' | '\n' | '*' => {
+                    self.section += 1;
+                    self.text.rest = tail;
+                }
+                'c' | 'C' | 'p' | 'P' if self.section > 0 => {
+                    self.text.rest = tail;
+                    return Some(self.code(None));
+                }
+                '<' | '(' => {
+                    let (name, tail) = control_text(tail);
+                    self.text.rest = tail;
+                    if let Some(tail) = tail.strip_prefix('=').filter(|_| self.section > 0) {
+                        self.text.rest = tail;
+                        let head = match code {
+                            '<' => ("name", resolve(name, &self.names)),
+                            _ => ("file", name.trim().to_string()),
+                        };
+                        return Some(self.code(Some(head)));
+                    }
+                }
+                '=' | '^' | '.' | ':' | 't' | 'q' => self.text.rest = control_text(tail).1,
+                _ => self.text.rest = tail,
+            }
+        }
+    }
+}
+
+impl ReadOut for Reader {
+    type Output<'a> = ReaderOut<'a>;
+}
+
+impl Read for Reader {
+    type Error = std::io::Error;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        let text = Text::new(src)?;
+        let mut names = vec![];
+        let mut rest = text.rest;
+        while let Some(pos) = rest.find('@') {
+            let Some(code) = rest[pos + 1..].chars().next() else {
+                break;
+            };
+            rest = &rest[pos + 1 + code.len_utf8()..];
+            match code {
+                '<' => {
+                    let (name, tail) = control_text(rest);
+                    let name = normalize(name);
+                    if !name.ends_with("...") && !names.contains(&name) {
+                        names.push(name);
+                    }
+                    rest = tail;
+                }
+                '(' | '=' | '^' | '.' | ':' | 't' | 'q' => rest = control_text(rest).1,
+                _ => (),
+            }
+        }
+        Ok(ReaderOut {
+            text,
+            lang: self.lang.clone(),
+            names,
+            section: 0,
+        })
+    }
+}
+
+/// Split the control text terminated by `@>` from the source, the rest is empty if it's unterminated.
+fn control_text(src: &str) -> (&str, &str) {
+    match src.find("@>") {
+        Some(pos) => (&src[..pos], &src[pos + 2..]),
+        None => (src, ""),
+    }
+}
+
+/// Normalize the spaces in the module name.
+fn normalize(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Expand the abbreviated module name to the full name, and normalize it.
+fn resolve(name: &str, names: &[String]) -> String {
+    let name = normalize(name);
+    match name.strip_suffix("...") {
+        Some(prefix) => names
+            .iter()
+            .find(|x| x.starts_with(prefix.trim_end()))
+            .cloned()
+            .unwrap_or(name),
+        None => name,
+    }
+}
+
+/// Handle the control codes in the code part, see [`Reader`] for more.
+fn tangle(code: &str, names: &[String]) -> String {
+    let mut res = String::new();
+    let mut rest = code;
+    while let Some(pos) = rest.find('@') {
+        res.push_str(&rest[..pos]);
+        let Some(ch) = rest[pos + 1..].chars().next() else {
+            res.push('@');
+            rest = "";
+            break;
+        };
+        let tail = &rest[pos + 1 + ch.len_utf8()..];
+        rest = match ch {
+            '@' => {
+                res.push('@');
+                tail
+            }
+            '<' => {
+                let (name, tail) = control_text(tail);
+                res.push_str("@<");
+                res.push_str(&resolve(name, names));
+                res.push_str("@>");
+                tail
+            }
+            '=' => {
+                let (text, tail) = control_text(tail);
+                res.push_str(&text.replace("@@", "@"));
+                tail
+            }
+            '^' | '.' | ':' | 't' | 'q' => control_text(tail).1,
+            ',' | '/' | '|' | '#' | '+' | ';' | '&' | '!' => tail,
+            _ => {
+                res.push('@');
+                res.push(ch);
+                tail
+            }
+        };
+    }
+    res.push_str(rest);
+    res
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::Reader;
+    use crate::codeblock::CodeBlock;
+    use crate::read::util::lines;
+    use crate::read::Read;
+
+    #[test]
+    fn sections() {
+        let mut src = "\
+Limbo @c with @<a@>= ignored.
+@ Text with |@<A   long
+ name@>| and an index@^entry @ here@>.
+@d N 1
+@<A long...@>= x@,y@=@@z@> @t\\quad@>@;
+@*Starred.
+@p
+@ @<A...@>=
+@ No code.
+@ Mail@@
+@c
+@q comment@>@<Undefined...@>@x
+"
+        .into();
+        let res = Reader::new()
+            .with_lang("pascal")
+            .read(&mut src)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(lines(&res), [5, 8, 9, 12]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(
+                    " xy@z \n",
+                    "pascal",
+                    vec![("name".into(), "A long name".into()), ("section".into(), "1".into())]
+                ),
+                CodeBlock::new("", "pascal", vec![("section".into(), "2".into())]),
+                CodeBlock::new(
+                    "",
+                    "pascal",
+                    vec![("name".into(), "A long name".into()), ("section".into(), "3".into())]
+                ),
+                CodeBlock::new("@<Undefined...@>@x\n", "pascal", vec![("section".into(), "5".into())]),
+            ]
+        );
+    }
+
+    #[test]
+    fn files() {
+        let mut src = "\
+@ The header.
+@( a.h @>=
+int a(void);
+@ @(a.h@>=
+@<Decls@>
+@ @<Decls@>=
+int b;
+"
+        .into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [3, 5, 7]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(
+                    "int a(void);\n",
+                    "c",
+                    vec![("file".into(), "a.h".into()), ("section".into(), "1".into())]
+                ),
+                CodeBlock::new(
+                    "@<Decls@>\n",
+                    "c",
+                    vec![("file".into(), "a.h".into()), ("section".into(), "2".into())]
+                ),
+                CodeBlock::new(
+                    "int b;\n",
+                    "c",
+                    vec![("name".into(), "Decls".into()), ("section".into(), "3".into())]
+                ),
+            ]
+        );
+    }
+}
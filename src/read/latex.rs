@@ -0,0 +1,324 @@
+/*!
+Read the code listings in [LaTeX] documents.
+
+[LaTeX]: https://www.latex-project.org/
+*/
+
+
+
+use std::borrow::Cow;
+
+use super::util::Text;
+use super::{Read, ReadOut, SourceCode};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Read the `lstlisting` and `minted` environments, and the verbatim-like environments, in LaTeX documents.
+
+The optional arguments of the environments, i.e. the key-value lists in the brackets, are the attributes,
+with the braces around the values removed;
+e.g. `\begin{lstlisting}[language=C, file=x.c, numbers]` is a block with the attributes
+`("file", "x.c")` and `("numbers", "")`, so it can be dispatched with `ByAttr::new("file")` directly.
+The language of the block is:
+
+- the value of the key `language` for `lstlisting`, which is not an attribute then;
+- the mandatory argument for `minted`, like `\begin{minted}[linenos]{rust}`;
+- the language of the environment given by [`with_env`](Self::with_env) for the verbatim-like environments,
+  which are `verbatim` and `Verbatim` without languages by default.
+
+The optional arguments are parsed only for `lstlisting`, `minted`,
+and the verbatim-like environments taking them, like `Verbatim` of fancyvrb;
+for the others like `verbatim`, the brackets after `\begin` are a part of the content.
+
+The content of a block starts from the line after `\begin`, unless there is text after the arguments,
+and ends before the line of `\end` if only spaces precede `\end` in the line.
+Environments after `%` comments and unterminated environments are ignored,
+and the macros like `\lstinputlisting` and `\mint` are not read.
+The [`span`](CodeBlock::span) of a code block is the range of its content.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::Read;
+use scribere::read::latex::Reader;
+
+let mut src = r"
+\section{Implementation}
+\begin{lstlisting}[language=C, file=src/main.c, caption={Main, the entry}]
+int main() {}
+\end{lstlisting}
+% \begin{verbatim}
+\begin{cppcode}
+int a;
+\end{cppcode}
+"
+.into();
+let blocks = Reader::new().with_env("cppcode", "cpp", false).read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(blocks[0].content, "int main() {}\n");
+assert_eq!(blocks[0].lang, "C");
+assert_eq!(
+    blocks[0].attrs,
+    [("file".into(), "src/main.c".into()), ("caption".into(), "Main, the entry".into())]
+);
+assert_eq!(blocks[0].span.as_ref().unwrap().line, 4);
+assert_eq!(blocks[1].lang, "cpp");
+```
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reader {
+    /// The verbatim-like environments, their languages, and whether they take the optional arguments.
+    envs: Vec<(String, String, bool)>,
+}
+
+impl Default for Reader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reader {
+    /// Construct a new reader with the default verbatim-like environments.
+    pub fn new() -> Self {
+        Self {
+            envs: vec![
+                ("verbatim".into(), "".into(), false),
+                ("Verbatim".into(), "".into(), true),
+            ],
+        }
+    }
+
+    /// Read the verbatim-like environment as code blocks in the language, which may be empty,
+    /// with the optional arguments like `[key=value]` as the attributes if `options` is set, and return self.
+    pub fn with_env(&mut self, name: impl Into<String>, lang: impl Into<String>, options: bool) -> &mut Self {
+        let (name, lang) = (name.into(), lang.into());
+        match self.envs.iter_mut().find(|x| x.0 == name) {
+            Some(x) => (x.1, x.2) = (lang, options),
+            None => self.envs.push((name, lang, options)),
+        }
+        self
+    }
+
+    /// Stop reading the verbatim-like environment and return self.
+    pub fn without_env(&mut self, name: &str) -> &mut Self {
+        self.envs.retain(|x| x.0 != name);
+        self
+    }
+}
+
+/**
+The output type of [`Reader::read`].
+*/
+#[derive(Debug, Clone)]
+pub struct ReaderOut<'a> {
+    /// The source being read.
+    text: Text<'a>,
+    /// The verbatim-like environments, their languages, and whether they take the optional arguments.
+    envs: Vec<(String, String, bool)>,
+}
+
+impl<'a> Iterator for ReaderOut<'a> {
+    type Item = CodeBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let pos = self.text.rest.find(r"\begin{")?;
+            let (before, after) = self.text.rest.split_at(pos);
+            self.text.rest = &after[r"\begin{".len()..];
+            if in_comment(before) {
+                continue;
+            }
+            let Some((name, mut tail)) = self.text.rest.split_once('}') else {
+                continue;
+            };
+            let mut lang = Cow::Borrowed("");
+            let mut attrs = vec![];
+            let opts = match name {
+                "lstlisting" => take_group(&mut tail, '[', ']'),
+                "minted" => {
+                    let opts = take_group(&mut tail, '[', ']');
+                    let Some(x) = take_group(&mut tail, '{', '}') else {
+                        continue;
+                    };
+                    lang = x.trim().into();
+                    opts
+                }
+                _ => match self.envs.iter().find(|x| x.0 == name) {
+                    Some(x) => {
+                        lang = x.1.clone().into();
+                        x.2.then(|| take_group(&mut tail, '[', ']')).flatten()
+                    }
+                    None => continue,
+                },
+            };
+            for (k, v) in opts.map(parse_opts).unwrap_or_default() {
+                match k {
+                    "language" if name == "lstlisting" => lang = v.into(),
+                    _ => attrs.push((k.into(), v.into())),
+                }
+            }
+            let line_end = tail.find('\n').map_or(tail.len(), |x| x + 1);
+            if tail[..line_end].trim().is_empty() {
+                tail = &tail[line_end..];
+            }
+            let close = format!(r"\end{{{name}}}");
+            let Some(end) = tail.find(&close) else {
+                continue;
+            };
+            let mut content = &tail[..end];
+            let line_start = content.rfind('\n').map_or(0, |x| x + 1);
+            if content[line_start..].trim().is_empty() {
+                content = &content[..line_start];
+            }
+            self.text.rest = &tail[end + close.len()..];
+            let offset = self.text.offset(tail);
+            let range = offset..offset + content.len();
+            let mut blk = CodeBlock::new(content, lang, attrs);
+            blk.span = Some(self.text.span(range));
+            return Some(blk);
+        }
+    }
+}
+
+impl ReadOut for Reader {
+    type Output<'a> = ReaderOut<'a>;
+}
+
+impl Read for Reader {
+    type Error = std::io::Error;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        Ok(ReaderOut {
+            text: Text::new(src)?,
+            envs: self.envs.clone(),
+        })
+    }
+}
+
+/// Check whether the end of the source is in a comment.
+fn in_comment(src: &str) -> bool {
+    let line = &src[src.rfind('\n').map_or(0, |x| x + 1)..];
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '%' => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Take the group delimited by the characters after the spaces, with the braces in it balanced.
+fn take_group<'a>(src: &mut &'a str, open: char, close: char) -> Option<&'a str> {
+    let group = src.trim_start_matches([' ', '\t']).strip_prefix(open)?;
+    let mut depth = 0usize;
+    for (i, ch) in group.char_indices() {
+        match ch {
+            _ if ch == close && depth == 0 => {
+                *src = &group[i + 1..];
+                return Some(&group[..i]);
+            }
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Parse the key-value list like `key=value, flag, key={some, value}`.
+fn parse_opts(opts: &str) -> Vec<(&str, &str)> {
+    let mut items = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, ch) in opts.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                items.push(&opts[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    items.push(&opts[start..]);
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let (k, v) = x.split_once('=').unwrap_or((x, ""));
+            let v = v.trim();
+            let v = v.strip_prefix('{').and_then(|x| x.strip_suffix('}')).unwrap_or(v);
+            (k.trim(), v)
+        })
+        .collect()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::Reader;
+    use crate::codeblock::CodeBlock;
+    use crate::read::util::lines;
+    use crate::read::Read;
+
+    #[test]
+    fn environments() {
+        let mut src = r"
+\begin{minted}[linenos, file={a.rs}]{rust}
+fn a() {}
+  \end{minted}
+50\% \begin{verbatim}x\end{verbatim}
+\begin{Verbatim}[numbers=left]
+  \begin{lstlisting}
+\end{Verbatim}
+\begin{lstlisting}
+\end{lstlisting}
+\begin{minted}
+\end{minted}
+\begin{unknown}
+\end{unknown}
+\begin{lstlisting}
+"
+        .into();
+        let res = Reader::new()
+            .without_env("verbatim")
+            .with_env("Verbatim", "text", true)
+            .read(&mut src)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(lines(&res), [3, 7, 10]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(
+                    "fn a() {}\n",
+                    "rust",
+                    vec![("linenos".into(), "".into()), ("file".into(), "a.rs".into())]
+                ),
+                CodeBlock::new(
+                    "  \\begin{lstlisting}\n",
+                    "text",
+                    vec![("numbers".into(), "left".into())]
+                ),
+                CodeBlock::new("", "", vec![]),
+            ]
+        );
+        let mut src = "\\begin{verbatim}[x]\nA\n\\end{verbatim}".into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [1]);
+        assert_eq!(res, [CodeBlock::new("[x]\nA\n", "", vec![])]);
+        let mut src = r"50\% \begin{verbatim}x\end{verbatim}".into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [1]);
+        assert_eq!(res, [CodeBlock::new("x", "", vec![])]);
+    }
+}
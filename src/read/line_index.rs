@@ -0,0 +1,91 @@
+/*!
+Locate the lines of byte offsets in the source.
+*/
+
+
+
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::codeblock::Span;
+
+
+
+/**
+The index of the starts of lines in the source,
+so that the [`Span`]s of byte ranges can be computed for the code blocks.
+
+Example:
+
+```
+use scribere::read::LineIndex;
+
+let idx = LineIndex::new("a\nbc\nd");
+let span = idx.span(None, 3..6);
+assert_eq!((span.line, span.column), (2, 2));
+```
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineIndex<'a> {
+    /// The source.
+    src: &'a str,
+    /// The byte offsets of the starts of all lines.
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Build the index of the source.
+    pub fn new(src: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { src, starts }
+    }
+
+    /// Get the source of the index.
+    pub fn source(&self) -> &'a str {
+        self.src
+    }
+
+    /// Get the line and the column of the byte offset, both starting from 1.
+    pub fn locate(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&x| x <= offset);
+        let start = self.starts[line - 1];
+        let column = self
+            .src
+            .get(start..offset)
+            .map_or(offset - start, |x| x.chars().count());
+        (line, column + 1)
+    }
+
+    /// Get the span of the byte range in the source at the path.
+    pub fn span<'p>(&self, path: Option<Cow<'p, Path>>, range: Range<usize>) -> Span<'p> {
+        let (line, column) = self.locate(range.start);
+        Span {
+            path,
+            start: range.start,
+            end: range.end,
+            line,
+            column,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::LineIndex;
+
+    #[test]
+    fn locate() {
+        let idx = LineIndex::new("ab\n\nλc\n");
+        assert_eq!(idx.locate(0), (1, 1));
+        assert_eq!(idx.locate(2), (1, 3));
+        assert_eq!(idx.locate(3), (2, 1));
+        assert_eq!(idx.locate(4), (3, 1));
+        assert_eq!(idx.locate(6), (3, 2));
+        assert_eq!(idx.locate(8), (4, 1));
+    }
+}
@@ -6,6 +6,18 @@ Reading from sources and return information in them.
 
 mod sourcecode;
 pub use self::sourcecode::SourceCode;
+mod line_index;
+pub use self::line_index::LineIndex;
+#[cfg(any(
+    feature = "read_asciidoc",
+    feature = "read_cmark",
+    feature = "read_cweb",
+    feature = "read_latex",
+    feature = "read_noweb",
+    feature = "read_org",
+    feature = "read_rst"
+))]
+mod util;
 use crate::codeblock::CodeBlock;
 
 
@@ -30,6 +42,27 @@ pub trait Read: ReadOut {
 
 
 
+#[cfg(feature = "read_asciidoc")]
+#[doc(cfg(feature = "read_asciidoc"))]
+pub mod asciidoc;
 #[cfg(feature = "read_cmark")]
 #[doc(cfg(feature = "read_cmark"))]
 pub mod cmark;
+#[cfg(feature = "read_cweb")]
+#[doc(cfg(feature = "read_cweb"))]
+pub mod cweb;
+#[cfg(feature = "read_latex")]
+#[doc(cfg(feature = "read_latex"))]
+pub mod latex;
+#[cfg(feature = "read_noweb")]
+#[doc(cfg(feature = "read_noweb"))]
+pub mod noweb;
+#[cfg(feature = "read_org")]
+#[doc(cfg(feature = "read_org"))]
+pub mod org;
+#[cfg(feature = "read_pandoc")]
+#[doc(cfg(feature = "read_pandoc"))]
+pub mod pandoc;
+#[cfg(feature = "read_rst")]
+#[doc(cfg(feature = "read_rst"))]
+pub mod rst;
@@ -0,0 +1,217 @@
+/*!
+Read the code chunks in [noweb] files.
+
+[noweb]: https://www.cs.tufts.edu/~nr/noweb/
+*/
+
+
+
+use std::borrow::Cow;
+
+use super::util::{next_line, Text};
+use super::{Read, ReadOut, SourceCode};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Read the code chunks, i.e. `<<name>>=` ... `@`, in noweb files.
+
+A code chunk starts with the line `<<name>>=`, and ends before the line starting with `@`,
+the start of the next code chunk, or the end of the file.
+The name of the chunk is the attribute `("name", name)`,
+so the references between the chunks can be expanded with [`Expander::new("name")`](crate::expand::Expander),
+and the root chunks named by the files can then be dispatched with `ByAttr::new("name")`.
+The escaped `@<<` and `@>>` in the chunks are kept in the content, so that they are never references,
+and the expander unescapes them with [`with_escape(Some("@"))`](crate::expand::Expander::with_escape).
+
+The language of the chunks is empty, or the one given by [`with_lang`](Self::with_lang),
+since noweb files don't carry the languages of the chunks.
+The `@@` at the beginning of a line in the chunks is unescaped to `@`,
+and the identifiers defined by `@ %def` are ignored.
+The [`span`](CodeBlock::span) of a code block is the range of its content.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::Read;
+use scribere::read::noweb::Reader;
+
+let mut src = "\
+\\section{Hello}
+The entry of the program.
+<<hello.c>>=
+int main() {
+    <<print hello>>
+}
+@ And the body.
+<<print hello>>=
+puts(\"hello\");
+"
+.into();
+let blocks = Reader::new().with_lang("c").read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(blocks[0].content, "int main() {\n    <<print hello>>\n}\n");
+assert_eq!(blocks[0].lang, "c");
+assert_eq!(blocks[0].attrs, [("name".into(), "hello.c".into())]);
+assert_eq!(blocks[0].span.as_ref().unwrap().line, 4);
+assert_eq!(blocks[1].attrs, [("name".into(), "print hello".into())]);
+```
+*/
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reader {
+    /// The language of the chunks.
+    lang: String,
+}
+
+impl Reader {
+    /// Construct a new reader, with the languages of the chunks empty.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the language of all chunks and return self.
+    pub fn with_lang(&mut self, lang: impl Into<String>) -> &mut Self {
+        self.lang = lang.into();
+        self
+    }
+}
+
+/**
+The output type of [`Reader::read`].
+*/
+#[derive(Debug, Clone)]
+pub struct ReaderOut<'a> {
+    /// The source being read.
+    text: Text<'a>,
+    /// The language of the chunks.
+    lang: String,
+}
+
+impl<'a> Iterator for ReaderOut<'a> {
+    type Item = CodeBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = loop {
+            if let Some(name) = parse_header(next_line(&mut self.text.rest)?) {
+                break name;
+            }
+        };
+        let start = self.text.rest;
+        let mut len = 0;
+        loop {
+            let before = self.text.rest;
+            match next_line(&mut self.text.rest) {
+                Some(line) if parse_header(line).is_some() => {
+                    self.text.rest = before;
+                    break;
+                }
+                Some(line) if is_terminator(line) => break,
+                Some(line) => len += line.len(),
+                None => break,
+            }
+        }
+        let content = &start[..len];
+        let offset = self.text.offset(start);
+        let mut blk = CodeBlock::new(unescape(content), self.lang.clone(), vec![("name".into(), name.into())]);
+        blk.span = Some(self.text.span(offset..offset + len));
+        Some(blk)
+    }
+}
+
+impl ReadOut for Reader {
+    type Output<'a> = ReaderOut<'a>;
+}
+
+impl Read for Reader {
+    type Error = std::io::Error;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        Ok(ReaderOut {
+            text: Text::new(src)?,
+            lang: self.lang.clone(),
+        })
+    }
+}
+
+/// Parse the line starting a code chunk and return the name of the chunk.
+fn parse_header(line: &str) -> Option<&str> {
+    line.trim_end().strip_prefix("<<")?.strip_suffix(">>=")
+}
+
+/// Check whether the line starts a documentation chunk.
+fn is_terminator(line: &str) -> bool {
+    line.strip_prefix('@')
+        .is_some_and(|x| x.is_empty() || x.starts_with(char::is_whitespace))
+}
+
+/// Unescape the `@@` at the beginning of lines in the content, borrow the content directly if there is none.
+fn unescape(content: &str) -> Cow<'_, str> {
+    if !content.split_inclusive('\n').any(|x| x.starts_with("@@")) {
+        return content.into();
+    }
+    content
+        .split_inclusive('\n')
+        .map(|x| x.strip_prefix('@').filter(|x| x.starts_with('@')).unwrap_or(x))
+        .collect::<String>()
+        .into()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::Reader;
+    use crate::codeblock::CodeBlock;
+    use crate::expand::Expander;
+    use crate::read::util::lines;
+    use crate::read::Read;
+
+    #[test]
+    fn chunks() {
+        let mut src = "\
+<<a>>= not a chunk
+<<*>>=
+a @<<b@>> c
+@@ not the end @@
+<<b>>=
+<<b>>=
+x
+@
+<<c>>=
+@ %def x
+doc
+"
+        .into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [3, 6, 7, 10]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new("a @<<b@>> c\n@ not the end @@\n", "", vec![("name".into(), "*".into())]),
+                CodeBlock::new("", "", vec![("name".into(), "b".into())]),
+                CodeBlock::new("x\n", "", vec![("name".into(), "b".into())]),
+                CodeBlock::new("", "", vec![("name".into(), "c".into())]),
+            ]
+        );
+    }
+
+    #[test]
+    fn escape() {
+        let mut src = "\
+<<*>>=
+print(\"@<<b@>>\")
+<<b>>
+<<b>>=
+X
+"
+        .into();
+        let blocks = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        let mut expander = Expander::new("name");
+        expander.with_escape(Some("@"));
+        assert_eq!(
+            expander.expand_errless(blocks).unwrap()[0].content,
+            "print(\"<<b>>\")\nX\n"
+        );
+    }
+}
@@ -0,0 +1,444 @@
+/*!
+Read the source blocks in [Org] documents.
+
+[Org]: https://orgmode.org/
+*/
+
+
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use super::util::{next_line, Text};
+use super::{Read, ReadOut, SourceCode};
+use crate::codeblock::CodeBlock;
+use crate::lang;
+
+
+
+/**
+Read the source blocks, i.e. `#+BEGIN_SRC` ... `#+END_SRC`, in Org documents.
+
+The first word after `#+BEGIN_SRC` is the language of the block,
+and the header arguments following it are the attributes, with the leading colon removed;
+e.g. `#+BEGIN_SRC c :tangle src/main.c :noweb yes` is a block in the language `c`,
+with the attributes `("tangle", "src/main.c")` and `("noweb", "yes")`,
+so it can be dispatched with `ByAttr::new("tangle")` directly.
+Name of the block given by the `#+NAME:` line just before it is the attribute `("name", name)`.
+
+The special values of `:tangle` are handled like Org:
+`:tangle yes` tangles the block to the file named by the stem of the document and the extension of the language,
+e.g. `doc.org` to `doc.rs` for `rust`, where the extension is given by [`lang::ext`](crate::lang::ext),
+or the language itself if it's unknown, e.g. `doc.zig` for `zig`;
+`:tangle no` doesn't tangle the block, so it's not read at all,
+unless it's named, and then it's read without the attribute `tangle`,
+so that it can still be referred by other blocks and [expanded](crate::expand::Expander).
+`:tangle yes` is the same as `:tangle no` if the source is not a file, since the target is unknown.
+
+The default header arguments set by `#+PROPERTY: header-args ...` and `#+PROPERTY: header-args:lang ...`
+(or appended with `header-args+`) anywhere in the document apply to all blocks (in the language),
+and the arguments of the block itself override them.
+Header arguments in property drawers are not supported.
+
+The common indentation of the content is removed,
+and the escaping commas before `*` and `#+` at the beginning of lines are removed too.
+The [`span`](CodeBlock::span) of a code block is the lines between `#+BEGIN_SRC` and `#+END_SRC`.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::Read;
+use scribere::read::org::Reader;
+
+let mut src = "\
+#+PROPERTY: header-args :noweb yes
+* Main
+#+BEGIN_SRC rust :tangle src/main.rs
+fn main() {}
+#+END_SRC
+"
+.into();
+let blocks = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(
+    blocks,
+    [CodeBlock::new("fn main() {}\n", "rust", vec![
+        ("noweb".into(), "yes".into()),
+        ("tangle".into(), "src/main.rs".into()),
+    ])]
+);
+assert_eq!(blocks[0].span.as_ref().unwrap().line, 4);
+```
+*/
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reader;
+
+impl Reader {
+    /// Construct a new reader.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/**
+The output type of [`Reader::read`].
+*/
+#[derive(Debug, Clone)]
+pub struct ReaderOut<'a> {
+    /// The source being read.
+    text: Text<'a>,
+    /// The default header arguments for all languages.
+    defaults: Vec<(String, String)>,
+    /// The default header arguments for specific languages.
+    lang_defaults: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl<'a> Iterator for ReaderOut<'a> {
+    type Item = CodeBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut name = None;
+        loop {
+            let line = next_line(&mut self.text.rest)?;
+            let trimmed = line.trim();
+            if let Some(x) = strip_keyword(trimmed, "#+name:") {
+                name = Some(x.trim());
+                continue;
+            }
+            let Some(header) = strip_keyword(trimmed, "#+begin_src") else {
+                if !trimmed.is_empty() {
+                    name = None;
+                }
+                continue;
+            };
+            if !header.is_empty() && !header.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let start = self.text.rest;
+            let mut lines = vec![];
+            let terminated = loop {
+                match next_line(&mut self.text.rest) {
+                    Some(line) if line.trim().eq_ignore_ascii_case("#+end_src") => break true,
+                    Some(line) => lines.push(line),
+                    None => break false,
+                }
+            };
+            if !terminated {
+                // Unterminated blocks are not blocks in Org
+                self.text.rest = start;
+                continue;
+            }
+            let (lang, args) = header
+                .trim()
+                .split_once(char::is_whitespace)
+                .unwrap_or((header.trim(), ""));
+            let mut attrs: Vec<(Cow<str>, Cow<str>)> = vec![];
+            let defaults = self.lang_defaults.iter().filter(|x| x.0 == lang).flat_map(|x| &x.1);
+            for (k, v) in self.defaults.iter().chain(defaults) {
+                set_attr(&mut attrs, k.clone().into(), v.clone().into());
+            }
+            for (k, v) in parse_args(args) {
+                set_attr(&mut attrs, k.into(), v.into());
+            }
+            if let Some(pos) = attrs.iter().position(|x| x.0 == "tangle") {
+                let target = match attrs[pos].1.as_ref() {
+                    "yes" => tangle_target(self.text.path(), lang),
+                    "no" => None,
+                    _ => Some(attrs[pos].1.to_string()),
+                };
+                match target {
+                    Some(x) => attrs[pos].1 = x.into(),
+                    None if name.is_some() => {
+                        attrs.remove(pos);
+                    }
+                    None => continue,
+                }
+            }
+            if let Some(name) = name {
+                set_attr(&mut attrs, "name".into(), name.into());
+            }
+            let offset = self.text.offset(start);
+            let range = offset..offset + lines.iter().map(|x| x.len()).sum::<usize>();
+            let mut blk = CodeBlock::new(unindent(start, &lines), lang, attrs);
+            blk.span = Some(self.text.span(range));
+            return Some(blk);
+        }
+    }
+}
+
+impl ReadOut for Reader {
+    type Output<'a> = ReaderOut<'a>;
+}
+
+impl Read for Reader {
+    type Error = std::io::Error;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        let text = Text::new(src)?;
+        let mut defaults = vec![];
+        let mut lang_defaults = vec![];
+        for line in text.rest.lines() {
+            let Some(prop) = strip_keyword(line.trim(), "#+property:") else {
+                continue;
+            };
+            let Some(prop) = strip_keyword(prop.trim_start(), "header-args") else {
+                continue;
+            };
+            let (append, prop) = match prop.strip_prefix('+') {
+                Some(x) => (true, x),
+                None => (false, prop),
+            };
+            let (target, args) = match prop.strip_prefix(':') {
+                Some(x) => {
+                    let (lang, args) = x.split_once(char::is_whitespace).unwrap_or((x, ""));
+                    let pos = match lang_defaults.iter().position(|x: &(String, _)| x.0 == lang) {
+                        Some(pos) => pos,
+                        None => {
+                            lang_defaults.push((lang.to_string(), vec![]));
+                            lang_defaults.len() - 1
+                        }
+                    };
+                    (&mut lang_defaults[pos].1, args)
+                }
+                None if prop.starts_with(char::is_whitespace) => (&mut defaults, prop),
+                None => continue,
+            };
+            if !append {
+                target.clear();
+            }
+            for (k, v) in parse_args(args) {
+                set_attr(target, k.to_string(), v.to_string());
+            }
+        }
+        Ok(ReaderOut {
+            text,
+            defaults,
+            lang_defaults,
+        })
+    }
+}
+
+/// Get the target of `:tangle yes` for the block in the language in the document.
+fn tangle_target(path: Option<&Path>, lang: &str) -> Option<String> {
+    let stem = path?.file_stem()?.to_str()?;
+    // Like Org, the language itself is the extension if it's unknown
+    let ext = lang::ext(lang).unwrap_or(lang);
+    (!ext.is_empty()).then(|| format!("{stem}.{ext}"))
+}
+
+/// Strip the case-insensitive keyword at the beginning of the line.
+fn strip_keyword<'a>(line: &'a str, kw: &str) -> Option<&'a str> {
+    match line.get(..kw.len()) {
+        Some(x) if x.eq_ignore_ascii_case(kw) => Some(&line[kw.len()..]),
+        _ => None,
+    }
+}
+
+/// Set the attribute, overriding the previous one with the same key.
+fn set_attr<T: PartialEq>(attrs: &mut Vec<(T, T)>, k: T, v: T) {
+    match attrs.iter_mut().find(|x| x.0 == k) {
+        Some(x) => x.1 = v,
+        None => attrs.push((k, v)),
+    }
+}
+
+/// Parse the header arguments like `:key value :flag :another "quoted value"`.
+///
+/// The value of an argument is all words until the next `:key`,
+/// with the quotes around it removed.
+/// Switches like `-n` before the arguments are ignored.
+fn parse_args(args: &str) -> Vec<(&str, &str)> {
+    let mut res = vec![];
+    let mut rest = args.trim_start();
+    while !rest.is_empty() {
+        let Some(arg) = rest.strip_prefix(':') else {
+            rest = rest.trim_start_matches(|x: char| !x.is_whitespace()).trim_start();
+            continue;
+        };
+        let key_end = arg.find(char::is_whitespace).unwrap_or(arg.len());
+        let (key, mut tail) = arg.split_at(key_end);
+        let mut value_end = 0;
+        loop {
+            let word = tail[value_end..].trim_start();
+            if word.is_empty() || word.starts_with(':') {
+                break;
+            }
+            let start = tail.len() - word.len();
+            let len = match word.strip_prefix('"').and_then(|x| x.find('"')) {
+                Some(x) => x + 2,
+                None => word.find(char::is_whitespace).unwrap_or(word.len()),
+            };
+            value_end = start + len;
+        }
+        let value = tail[..value_end].trim();
+        tail = &tail[value_end..];
+        let value = value
+            .strip_prefix('"')
+            .and_then(|x| x.strip_suffix('"'))
+            .unwrap_or(value);
+        res.push((key, value));
+        rest = tail.trim_start();
+    }
+    res
+}
+
+/// Remove the common indentation and the escaping commas of the lines,
+/// borrow the source directly if nothing is to be removed.
+fn unindent<'a>(start: &'a str, lines: &[&'a str]) -> Cow<'a, str> {
+    let indent = lines
+        .iter()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| x.len() - x.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let escaped = |x: &str| {
+        let x = x.trim_start();
+        x.starts_with(",*") || x.starts_with(",#+")
+    };
+    if indent == 0 && !lines.iter().any(|x| escaped(x)) {
+        return start[..lines.iter().map(|x| x.len()).sum()].into();
+    }
+    let mut res = String::new();
+    for line in lines {
+        let line = line
+            .get(indent..)
+            .unwrap_or_else(|| line.trim_start_matches([' ', '\t']));
+        if escaped(line) {
+            let pos = line.find(',').expect("The line is escaped");
+            res.push_str(&line[..pos]);
+            res.push_str(&line[pos + 1..]);
+        } else {
+            res.push_str(line);
+        }
+    }
+    res.into()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{tangle_target, Reader};
+    use crate::codeblock::CodeBlock;
+    use crate::directory::dummydir::DummyDir;
+    use crate::dispatch::{ByAttr, DispatchErrless};
+    use crate::expand::Expander;
+    use crate::read::util::lines;
+    use crate::read::Read;
+    use crate::write_blocks::write_blocks_errless;
+
+    #[test]
+    fn header_args() {
+        let mut src = "\
+#+PROPERTY: header-args :tangle no
+#+property: header-args:python :tangle a.py :shebang \"#!/usr/bin/env python\"
+#+PROPERTY: header-args+ :noweb yes
+
+#+NAME: hello
+#+begin_src python
+print(1)
+<<util>>
+#+end_src
+
+#+BEGIN_SRC c -n :tangle main.c :flag
+int main() {}
+#+END_SRC
+
+#+BEGIN_SRC sh
+echo untangled
+#+END_SRC
+
+#+NAME: util
+#+BEGIN_SRC python :tangle yes
+print(2)
+#+END_SRC
+"
+        .into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [7, 12, 21]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(
+                    "print(1)\n<<util>>\n",
+                    "python",
+                    vec![
+                        ("tangle".into(), "a.py".into()),
+                        ("noweb".into(), "yes".into()),
+                        ("shebang".into(), "#!/usr/bin/env python".into()),
+                        ("name".into(), "hello".into()),
+                    ]
+                ),
+                CodeBlock::new(
+                    "int main() {}\n",
+                    "c",
+                    vec![
+                        ("tangle".into(), "main.c".into()),
+                        ("noweb".into(), "yes".into()),
+                        ("flag".into(), "".into()),
+                    ]
+                ),
+                CodeBlock::new(
+                    "print(2)\n",
+                    "python",
+                    vec![
+                        ("noweb".into(), "yes".into()),
+                        ("shebang".into(), "#!/usr/bin/env python".into()),
+                        ("name".into(), "util".into()),
+                    ]
+                ),
+            ]
+        );
+        // No block is tangled to the files named `no` or `yes`
+        let blocks = Expander::new("name").expand_errless(res).unwrap();
+        let mut dir = DummyDir::new();
+        write_blocks_errless(ByAttr::new("tangle").dispatch(blocks.into_iter()), &mut dir).unwrap();
+        let mut files = dir.into_iter().collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                (PathBuf::from("a.py"), b"print(1)\nprint(2)\n".to_vec()),
+                (PathBuf::from("main.c"), b"int main() {}\n".to_vec()),
+            ]
+        );
+        assert_eq!(tangle_target(Some(Path::new("dir/doc.org")), "rust").unwrap(), "doc.rs");
+        assert_eq!(
+            tangle_target(Some(Path::new("doc.org")), "emacs-lisp").unwrap(),
+            "doc.el"
+        );
+        // The unknown languages are the extensions themselves
+        assert_eq!(tangle_target(Some(Path::new("doc.org")), "zig").unwrap(), "doc.zig");
+        assert_eq!(tangle_target(Some(Path::new("doc.org")), ""), None);
+        assert_eq!(tangle_target(None, "rust"), None);
+    }
+
+    #[test]
+    fn content() {
+        let mut src = "\
+- item
+  #+BEGIN_SRC sh
+    echo 1
+  ,* not a heading
+      echo 2
+  #+END_SRC
+#+NAME: not followed
+text
+#+BEGIN_SRC
+#+END_SRC
+#+BEGIN_SRC unterminated
+"
+        .into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [3, 10]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new("  echo 1\n* not a heading\n    echo 2\n", "sh", vec![]),
+                CodeBlock::new("", "", vec![]),
+            ]
+        );
+    }
+}
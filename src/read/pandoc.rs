@@ -0,0 +1,278 @@
+/*!
+Read the JSON AST produced by [pandoc].
+
+Pandoc can convert documents in lots of formats into its JSON AST (with `pandoc -t json`),
+so this reader enables extracting code blocks from those documents, e.g. docx, RST or LaTeX,
+without parsing the formats here.
+
+[pandoc]: https://pandoc.org/
+*/
+
+
+
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{Read, ReadOut, SourceCode};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+The error when reading the pandoc JSON AST.
+*/
+#[derive(Debug, Error)]
+pub enum ReadError {
+    /// The error when reading the source file.
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+    /// The source is not valid JSON.
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    /// The content of a `CodeBlock` node is not of the expected shape.
+    #[error("malformed code block: {0}")]
+    MalformedBlock(Value),
+}
+
+
+
+/**
+Read the code blocks in the pandoc JSON AST.
+
+Each `CodeBlock` node in the blocks of the document is a code block,
+no matter how deep it's nested in other blocks like `Div`, `BlockQuote`, lists, figures and tables,
+in the order of the AST.
+The blocks in the metadata and the footnotes are not read.
+The attributes of the node are mapped into [`CodeBlock::attrs`]
+in the same way as [the info strings of markdown](super::cmark::parse_info),
+that is, the identifier is the attribute `("id", identifier)`,
+each class is an attribute `("class", class)`,
+and the key-value pairs are kept as is;
+and the first class is the language of the code block.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::Read;
+use scribere::read::pandoc::Reader;
+
+// `pandoc -t json` with "```{.rust file=a.rs}\nfn main() {}\n```"
+let mut src = r#"{"pandoc-api-version":[1,23],"meta":{},"blocks":[
+    {"t":"CodeBlock","c":[["",["rust"],[["file","a.rs"]]],"fn main() {}"]}
+]}"#.into();
+assert_eq!(
+    Reader::new().read(&mut src).unwrap().collect::<Vec<_>>(),
+    [CodeBlock::new("fn main() {}\n", "rust", vec![
+        ("class".into(), "rust".into()),
+        ("file".into(), "a.rs".into()),
+    ])]
+);
+```
+
+Note that pandoc strips the trailing newline of the code,
+so it's added back here to make the blocks in the same form as other readers.
+And since there is no position in the AST, the [`span`](CodeBlock::span) of the blocks is `None`.
+*/
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reader;
+
+impl Reader {
+    /// Construct a new reader.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ReadOut for Reader {
+    type Output<'a> = std::vec::IntoIter<CodeBlock<'a>>;
+}
+
+impl Read for Reader {
+    type Error = ReadError;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        src.to_code()?;
+        let ast = serde_json::from_str::<Value>(src.as_code().expect("`src` should be code after conversion"))?;
+        let mut res = vec![];
+        collect_blocks(&ast["blocks"], &mut res)?;
+        Ok(res.into_iter())
+    }
+}
+
+/// Iterate over the elements of the array, nothing if it's not an array.
+fn items(val: &Value) -> impl Iterator<Item = &Value> {
+    val.as_array().into_iter().flatten()
+}
+
+/// Collect the `CodeBlock` nodes in the list of blocks into `res`, in the order of the AST.
+fn collect_blocks(blocks: &Value, res: &mut Vec<CodeBlock<'_>>) -> Result<(), ReadError> {
+    for blk in items(blocks) {
+        let ctnt = &blk["c"];
+        match blk["t"].as_str() {
+            Some("CodeBlock") => res.push(to_block(ctnt).ok_or_else(|| ReadError::MalformedBlock(ctnt.clone()))?),
+            Some("BlockQuote") => collect_blocks(ctnt, res)?,
+            // `[[Block]]`, after the list attributes for ordered lists
+            Some("BulletList") => collect_lists(ctnt, res)?,
+            Some("OrderedList") => collect_lists(&ctnt[1], res)?,
+            // `[([Inline], [[Block]])]`
+            Some("DefinitionList") => {
+                for item in items(ctnt) {
+                    collect_lists(&item[1], res)?;
+                }
+            }
+            // `Attr [Block]`
+            Some("Div") => collect_blocks(&ctnt[1], res)?,
+            // `Attr Caption [Block]`
+            Some("Figure") => {
+                collect_blocks(&ctnt[1][1], res)?;
+                collect_blocks(&ctnt[2], res)?;
+            }
+            // `Attr Caption [ColSpec] TableHead [TableBody] TableFoot`
+            Some("Table") => {
+                collect_blocks(&ctnt[1][1], res)?;
+                collect_rows(&ctnt[3][1], res)?;
+                for body in items(&ctnt[4]) {
+                    collect_rows(&body[2], res)?;
+                    collect_rows(&body[3], res)?;
+                }
+                collect_rows(&ctnt[5][1], res)?;
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Collect the `CodeBlock` nodes in the list of lists of blocks, e.g. the items of a list.
+fn collect_lists(lists: &Value, res: &mut Vec<CodeBlock<'_>>) -> Result<(), ReadError> {
+    items(lists).try_for_each(|x| collect_blocks(x, res))
+}
+
+/// Collect the `CodeBlock` nodes in the rows of a table, i.e. `[Attr, [Cell]]`,
+/// where a cell is `[Attr, Alignment, RowSpan, ColSpan, [Block]]`.
+fn collect_rows(rows: &Value, res: &mut Vec<CodeBlock<'_>>) -> Result<(), ReadError> {
+    for row in items(rows) {
+        for cell in items(&row[1]) {
+            collect_blocks(&cell[4], res)?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert the content of a `CodeBlock` node, i.e. `[[id, [class], [[key, value]]], code]`, into a code block.
+fn to_block(ctnt: &Value) -> Option<CodeBlock<'static>> {
+    let [attr, code] = ctnt.as_array()?.as_slice() else {
+        return None;
+    };
+    let [id, classes, kvs] = attr.as_array()?.as_slice() else {
+        return None;
+    };
+    let mut attrs = vec![];
+    let id = id.as_str()?;
+    if !id.is_empty() {
+        attrs.push(("id".into(), id.to_string().into()));
+    }
+    let classes = classes.as_array()?;
+    for class in classes {
+        attrs.push(("class".into(), class.as_str()?.to_string().into()));
+    }
+    for kv in kvs.as_array()? {
+        let [k, v] = kv.as_array()?.as_slice() else {
+            return None;
+        };
+        attrs.push((k.as_str()?.to_string().into(), v.as_str()?.to_string().into()));
+    }
+    let mut code = code.as_str()?.to_string();
+    if !code.is_empty() {
+        code.push('\n');
+    }
+    let lang = classes.first().and_then(Value::as_str).unwrap_or_default().to_string();
+    Some(CodeBlock::new(code, lang, attrs))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{ReadError, Reader};
+    use crate::codeblock::CodeBlock;
+    use crate::read::Read;
+
+    #[test]
+    fn nested() {
+        let mut src = r#"{"pandoc-api-version":[1,23],"meta":{},"blocks":[
+            {"t":"Para","c":[{"t":"Str","c":"text"}]},
+            {"t":"CodeBlock","c":[["main",[],[]],"int main() {}"]},
+            {"t":"BlockQuote","c":[
+                {"t":"CodeBlock","c":[["",["py","ignore"],[["file","a.py"]]],"print(1)\nprint(2)"]}
+            ]},
+            {"t":"BulletList","c":[[{"t":"CodeBlock","c":[["",[],[]],""]}]]}
+        ]}"#
+        .into();
+        assert_eq!(
+            Reader::new().read(&mut src).unwrap().collect::<Vec<_>>(),
+            [
+                CodeBlock::new("int main() {}\n", "", vec![("id".into(), "main".into())]),
+                CodeBlock::new(
+                    "print(1)\nprint(2)\n",
+                    "py",
+                    vec![
+                        ("class".into(), "py".into()),
+                        ("class".into(), "ignore".into()),
+                        ("file".into(), "a.py".into()),
+                    ]
+                ),
+                CodeBlock::new("", "", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn order() {
+        let attr = r#"["",[],[]]"#;
+        let code = |x: &str| format!(r#"{{"t":"CodeBlock","c":[{attr},"{x}"]}}"#);
+        let cell = |x: &str| format!(r#"[{attr},{{"t":"AlignDefault"}},1,1,[{}]]"#, code(x));
+        let src = format!(
+            r#"{{"pandoc-api-version":[1,23],"blocks":[
+                {{"t":"Div","c":[{attr},[{},{{"t":"Div","c":[{attr},[{}]]}}]]}},
+                {{"t":"OrderedList","c":[[1,{{"t":"Decimal"}},{{"t":"Period"}}],[[{}],[{}]]]}},
+                {{"t":"Table","c":[
+                    {attr},[null,[]],[[{{"t":"AlignDefault"}},{{"t":"ColWidthDefault"}}]],
+                    [{attr},[[{attr},[{}]]]],
+                    [[{attr},0,[],[[{attr},[{}]]]]],
+                    [{attr},[]]
+                ]}},
+                {{"t":"Para","c":[{{"t":"Note","c":[{}]}}]}}
+            ],"meta":{{"a":{{"t":"MetaBlocks","c":[{}]}}}}}}"#,
+            code("1"),
+            code("2"),
+            code("3"),
+            code("4"),
+            cell("5"),
+            cell("6"),
+            code("note"),
+            code("meta"),
+        );
+        let mut src = src.as_str().into();
+        assert_eq!(
+            Reader::new()
+                .read(&mut src)
+                .unwrap()
+                .map(|x| x.content.into_owned())
+                .collect::<Vec<_>>(),
+            ["1\n", "2\n", "3\n", "4\n", "5\n", "6\n"]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let mut src = r#"{"blocks":[{"t":"CodeBlock","c":["no attributes"]}]}"#.into();
+        assert!(matches!(
+            Reader::new().read(&mut src),
+            Err(ReadError::MalformedBlock(_))
+        ));
+        let mut src = "{".into();
+        assert!(matches!(Reader::new().read(&mut src), Err(ReadError::JsonError(_))));
+    }
+}
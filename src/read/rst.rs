@@ -0,0 +1,329 @@
+/*!
+Read the code blocks in [reStructuredText] documents.
+
+[reStructuredText]: https://docutils.sourceforge.io/rst.html
+*/
+
+
+
+use std::borrow::Cow;
+
+use super::util::{next_line, Text};
+use super::{Read, ReadOut, SourceCode};
+use crate::codeblock::CodeBlock;
+
+
+
+/**
+Read the code blocks, i.e. the `code-block`, `code` and `sourcecode` directives, in reStructuredText documents.
+
+The argument of the directive is the language of the block,
+and the options of the directive are the attributes;
+e.g. `.. code-block:: python` with the option `:file: src/main.py` is a block in the language `python`,
+with the attribute `("file", "src/main.py")`, so it can be dispatched with `ByAttr::new("file")` directly.
+Options without values like `:linenos:` are the attributes with empty values,
+and the option `:class:` is split into one attribute `("class", class)` for each class.
+Options spanning multiple lines are not supported.
+
+Literal blocks, i.e. the indented blocks after paragraphs ending with `::`,
+are read as code blocks without the language and the attributes
+only if enabled with [`with_literal_blocks`](Self::with_literal_blocks).
+Quoted literal blocks are not supported.
+
+The common indentation of the content is removed, and so are the blank lines around it.
+The [`span`](CodeBlock::span) of a code block is the range of its content,
+or the end of the directive if it's empty.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::Read;
+use scribere::read::rst::Reader;
+
+let text = "\
+Main
+====
+
+.. code-block:: python
+   :name: main
+   :file: src/main.py
+
+   def main():
+       pass
+
+Run it like::
+
+   $ python src/main.py
+";
+let mut src = text.into();
+let blocks = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(blocks.len(), 1);
+assert_eq!(blocks[0].content, "def main():\n    pass\n");
+assert_eq!(blocks[0].lang, "python");
+assert_eq!(
+    blocks[0].attrs,
+    [("name".into(), "main".into()), ("file".into(), "src/main.py".into())]
+);
+assert_eq!(blocks[0].span.as_ref().unwrap().line, 8);
+
+let mut src = text.into();
+let blocks = Reader::new().with_literal_blocks(true).read(&mut src).unwrap().collect::<Vec<_>>();
+assert_eq!(blocks[1].content, "$ python src/main.py\n");
+```
+*/
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reader {
+    /// Whether to read the literal blocks.
+    literal: bool,
+}
+
+impl Reader {
+    /// Construct a new reader, ignoring the literal blocks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether to read the literal blocks and return self.
+    pub fn with_literal_blocks(&mut self, literal: bool) -> &mut Self {
+        self.literal = literal;
+        self
+    }
+}
+
+/**
+The output type of [`Reader::read`].
+*/
+#[derive(Debug, Clone)]
+pub struct ReaderOut<'a> {
+    /// The source being read.
+    text: Text<'a>,
+    /// Whether to read the literal blocks.
+    literal: bool,
+}
+
+impl<'a> ReaderOut<'a> {
+    /// Take the lines indented more than the indentation as the content of a code block.
+    fn body(&mut self, indent: usize, lang: &'a str, attrs: Vec<(Cow<'a, str>, Cow<'a, str>)>) -> CodeBlock<'a> {
+        let mut lines = vec![];
+        let mut start = None;
+        loop {
+            let before = self.text.rest;
+            match next_line(&mut self.text.rest) {
+                Some(line) if line.trim().is_empty() => {
+                    if start.is_some() {
+                        lines.push(line);
+                    }
+                }
+                Some(line) if indent_of(line) > indent => {
+                    start.get_or_insert(before);
+                    lines.push(line);
+                }
+                _ => {
+                    self.text.rest = before;
+                    break;
+                }
+            }
+        }
+        while lines.last().is_some_and(|x| x.trim().is_empty()) {
+            lines.pop();
+        }
+        let start = start.unwrap_or(self.text.rest);
+        let offset = self.text.offset(start);
+        let range = offset..offset + lines.iter().map(|x| x.len()).sum::<usize>();
+        let mut blk = CodeBlock::new(unindent(start, &lines), lang, attrs);
+        blk.span = Some(self.text.span(range));
+        blk
+    }
+}
+
+impl<'a> Iterator for ReaderOut<'a> {
+    type Item = CodeBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = next_line(&mut self.text.rest)?;
+            let trimmed = line.trim();
+            if let Some(lang) = parse_directive(trimmed) {
+                let mut attrs = vec![];
+                loop {
+                    let before = self.text.rest;
+                    match next_line(&mut self.text.rest) {
+                        Some(x) if indent_of(x) > indent_of(line) && x.trim().starts_with(':') => {
+                            parse_option(x.trim(), &mut attrs)
+                        }
+                        _ => {
+                            self.text.rest = before;
+                            break;
+                        }
+                    }
+                }
+                return Some(self.body(indent_of(line), lang, attrs));
+            }
+            if trimmed.ends_with("::") && !trimmed.starts_with("..") {
+                // Literal blocks are always taken, so that the directives in them are not read
+                let blk = self.body(indent_of(line), "", vec![]);
+                if self.literal && !blk.content.is_empty() {
+                    return Some(blk);
+                }
+            }
+        }
+    }
+}
+
+impl ReadOut for Reader {
+    type Output<'a> = ReaderOut<'a>;
+}
+
+impl Read for Reader {
+    type Error = std::io::Error;
+
+    fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+        Ok(ReaderOut {
+            text: Text::new(src)?,
+            literal: self.literal,
+        })
+    }
+}
+
+/// Get the indentation of the line in bytes.
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+/// Parse the directive line of a code block and return its language.
+fn parse_directive(line: &str) -> Option<&str> {
+    let (name, arg) = line.strip_prefix("..")?.split_once("::")?;
+    let name = name.strip_prefix(char::is_whitespace)?.trim();
+    ["code-block", "code", "sourcecode"]
+        .iter()
+        .any(|x| x.eq_ignore_ascii_case(name))
+        .then(|| arg.trim())
+}
+
+/// Parse the option line like `:key: value` of a directive into the attributes.
+fn parse_option<'a>(line: &'a str, attrs: &mut Vec<(Cow<'a, str>, Cow<'a, str>)>) {
+    let Some((key, value)) = line.strip_prefix(':').and_then(|x| x.split_once(':')) else {
+        return;
+    };
+    let value = value.trim();
+    if key == "class" {
+        attrs.extend(value.split_whitespace().map(|x| ("class".into(), x.into())));
+    } else {
+        attrs.push((key.into(), value.into()));
+    }
+}
+
+/// Remove the common indentation of the lines,
+/// borrow the source directly if the lines are one line or nothing is to be removed.
+fn unindent<'a>(start: &'a str, lines: &[&'a str]) -> Cow<'a, str> {
+    let indent = lines
+        .iter()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| indent_of(x))
+        .min()
+        .unwrap_or(0);
+    match lines {
+        [] => "".into(),
+        [line] => line[indent..].into(),
+        _ if indent == 0 => start[..lines.iter().map(|x| x.len()).sum()].into(),
+        _ => lines
+            .iter()
+            .map(|line| {
+                line.get(indent..)
+                    .unwrap_or_else(|| line.trim_start_matches([' ', '\t']))
+            })
+            .collect::<String>()
+            .into(),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::Reader;
+    use crate::codeblock::CodeBlock;
+    use crate::read::util::lines;
+    use crate::read::Read;
+
+    #[test]
+    fn directives() {
+        let src = "\
+.. note::
+
+   .. CODE:: c
+      :class: a b
+      :linenos:
+
+        int a;
+
+      int b;
+
+
+.. sourcecode::
+.. code-block:: rust
+text
+.. code-blocks:: x
+
+   y
+..code:: x
+
+   y
+";
+        let mut src = src.into();
+        let res = Reader::new().read(&mut src).unwrap().collect::<Vec<_>>();
+        assert_eq!(lines(&res), [7, 13, 14]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(
+                    "  int a;\n\nint b;\n",
+                    "c",
+                    vec![
+                        ("class".into(), "a".into()),
+                        ("class".into(), "b".into()),
+                        ("linenos".into(), "".into()),
+                    ]
+                ),
+                CodeBlock::new("", "", vec![]),
+                CodeBlock::new("", "rust", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn literal() {
+        let src = "\
+Paragraph::
+
+    .. code:: x
+
+       y
+
+Another::
+
+    z
+Not a literal block::
+";
+        let mut text = src.into();
+        assert_eq!(Reader::new().read(&mut text).unwrap().count(), 0);
+        let mut text = src.into();
+        let res = Reader::new()
+            .with_literal_blocks(true)
+            .read(&mut text)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(lines(&res), [3, 9]);
+        assert_eq!(
+            res,
+            [
+                CodeBlock::new(".. code:: x\n\n   y\n", "", vec![]),
+                CodeBlock::new("z\n", "", vec![]),
+            ]
+        );
+        assert!(matches!(res[1].content, Cow::Borrowed("z\n")));
+    }
+}
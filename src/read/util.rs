@@ -0,0 +1,100 @@
+/*!
+Helpers shared by the readers of text sources.
+*/
+
+
+
+use std::borrow::Cow;
+use std::ops::Range;
+#[cfg(feature = "read_org")]
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::{LineIndex, SourceCode};
+use crate::codeblock::Span;
+
+
+
+/**
+The source being read by a reader of text,
+with the index of lines and the path to the source file for the spans of the code blocks.
+*/
+#[derive(Debug, Clone)]
+pub(super) struct Text<'a> {
+    /// The remaining source.
+    pub(super) rest: &'a str,
+    /// The index of lines in the source.
+    index: LineIndex<'a>,
+    /// The path to the source file.
+    path: Option<PathBuf>,
+}
+
+impl<'a> Text<'a> {
+    /// Convert the source into code, and start reading it from the beginning.
+    pub(super) fn new(src: &'a mut SourceCode<'a, '_>) -> Result<Self, std::io::Error> {
+        let path = src.as_file().map(|x| x.to_path_buf());
+        src.to_code()?;
+        let src = src.as_code().expect("`src` should be code after conversion");
+        Ok(Self {
+            rest: src,
+            index: LineIndex::new(src),
+            path,
+        })
+    }
+
+    /// Get the path to the source file, `None` if the source is not a file.
+    #[cfg(feature = "read_org")]
+    pub(super) fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Get the byte offset of the suffix of the source.
+    #[cfg(any(
+        feature = "read_asciidoc",
+        feature = "read_cweb",
+        feature = "read_latex",
+        feature = "read_noweb",
+        feature = "read_org",
+        feature = "read_rst"
+    ))]
+    pub(super) fn offset(&self, tail: &str) -> usize {
+        self.index.source().len() - tail.len()
+    }
+
+    /// Get the span of the byte range in the source.
+    pub(super) fn span(&self, range: Range<usize>) -> Span<'static> {
+        self.index.span(self.path.clone().map(Cow::Owned), range)
+    }
+}
+
+/// Take the next line, including the line break, from the source.
+#[cfg(any(
+    feature = "read_asciidoc",
+    feature = "read_noweb",
+    feature = "read_org",
+    feature = "read_rst"
+))]
+pub(super) fn next_line<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    if rest.is_empty() {
+        return None;
+    }
+    let (line, tail) = rest.split_at(rest.find('\n').map_or(rest.len(), |x| x + 1));
+    *rest = tail;
+    Some(line)
+}
+
+/// Get the lines of the spans of the blocks.
+#[cfg(all(
+    test,
+    any(
+        feature = "read_asciidoc",
+        feature = "read_cweb",
+        feature = "read_latex",
+        feature = "read_noweb",
+        feature = "read_org",
+        feature = "read_rst"
+    )
+))]
+pub(super) fn lines(blocks: &[crate::codeblock::CodeBlock]) -> Vec<usize> {
+    blocks.iter().map(|x| x.span.as_ref().unwrap().line).collect()
+}
@@ -4,8 +4,9 @@ Utilities to read all files in a directory and dispatch them into files.
 
 
 
+use std::borrow::Cow;
 use std::fmt::Debug;
-use std::path::Path;
+use std::path::PathBuf;
 use std::pin::Pin;
 
 use thiserror::Error;
@@ -49,8 +50,11 @@ pub struct DirIter<'a, D: Directory + 'a, R: Read> {
     src: Vec<Pin<Box<SourceCode<'a, 'a>>>>,
     /// Iterators over the code blocks in sources yielded by `files` so far.
     blocks: Vec<R::Output<'a>>,
-    /// Current dispatch target. Will be taken in the next iteration.
-    target: Option<&'a Path>,
+    /// Current dispatch target, i.e. the relative path of the current source.
+    /// Will be taken in the next iteration.
+    target: Option<PathBuf>,
+    /// The path to the source being read, or its relative path if it's not a file.
+    path: Option<PathBuf>,
 }
 
 impl<'a, D: Directory + 'a, R: Read + 'a> Iterator for DirIter<'a, D, R>
@@ -61,15 +65,23 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(ref mut iter) = self.blocks.last_mut() {
-            if let Some(blk) = iter.next() {
+            if let Some(mut blk) = iter.next() {
+                if let Some(ref mut span) = blk.span {
+                    if span.path.is_none() {
+                        span.path = self.path.clone().map(Cow::Owned);
+                    }
+                }
                 return Some(Ok(Event::new(self.target.take(), blk)));
             }
         }
-        self.src.push(match self.files.next()? {
-            Ok(s) => Box::pin(s),
+        let (target, src) = match self.files.next()? {
+            Ok(x) => x,
             Err(e) => return Some(Err(IterError::WalkError(e))),
-        });
+        };
+        self.src.push(Box::pin(src));
         let src = self.src.last_mut().expect("It's just pushed").as_mut().get_mut();
+        self.path = Some(src.as_file().map_or_else(|| target.clone(), |x| x.to_path_buf()));
+        self.target = Some(target);
         // Safety: There are two requirements for the safety of this transmution:
         // 1. src outlives `'a`: Since `Self: 'a`, and it's borrowed from self, this is guaranteed.
         // 2. src is only borrowed once during `'a`: It's only borrowed here,
@@ -87,22 +99,151 @@ where
 Read the content in the directory with a reader,
 returning an event iterator dispatching them to the same relative path.
 
+That is, the target of the first code block in each source
+is the path to the source relative to the directory,
+and the following code blocks in the source are dispatched to the same target.
+The sources without code blocks are simply skipped.
+
+If the reader can't tell the path to the source file of a code block,
+the path in its [`span`](crate::CodeBlock::span) is filled with the path of the file,
+or the relative path of the source if it's not a file, e.g. in a [`DummyDir`](crate::directory::dummydir::DummyDir).
+
 Usually the targets yielded by the result event iterator need to be processed
 to have prettier names, or, for example, for a file in the source directory named `src/a.md`,
 code blocks in it would be dispatched to `src/a.md` too (both pathes are relative path),
-which is usually not what's expected.
+which is usually not what's expected;
+this can be done with [`RewriteTargets`](crate::dispatch::RewriteTargets).
 */
-pub fn read_dir<D: Directory, R: Read>(dir: &mut D, reader: R) -> Result<DirIter<D, R>, D::OpenError> {
+pub fn read_dir<D: Directory, R: Read>(dir: &mut D, reader: R) -> Result<DirIter<'_, D, R>, D::OpenError> {
     Ok(DirIter {
         files: dir.walk()?,
         reader,
         blocks: vec![],
         target: None,
         src: vec![],
+        path: None,
     })
 }
 
 
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::read_dir;
+    use crate::codeblock::CodeBlock;
+    use crate::directory::dummydir::DummyDir;
+    use crate::dispatch::Event;
+    use crate::read::{LineIndex, Read, ReadOut, SourceCode};
+
+    /// A reader taking every line of the source as a code block, with the span but not the path.
+    #[derive(Debug, Clone, Copy)]
+    struct Lines;
+
+    impl ReadOut for Lines {
+        type Output<'a> = std::vec::IntoIter<CodeBlock<'a>>;
+    }
+
+    impl Read for Lines {
+        type Error = std::io::Error;
+
+        fn read<'a>(&mut self, src: &'a mut SourceCode<'a, '_>) -> Result<Self::Output<'a>, Self::Error> {
+            src.to_code()?;
+            let src = src.as_code().expect("`src` should be code after conversion");
+            let index = LineIndex::new(src);
+            let mut offset = 0;
+            Ok(src
+                .split_inclusive('\n')
+                .map(|x| {
+                    let mut blk = CodeBlock::new(x, "", vec![]);
+                    blk.span = Some(index.span(None, offset..offset + x.len()));
+                    offset += x.len();
+                    blk
+                })
+                .collect::<Vec<_>>()
+                .into_iter())
+        }
+    }
+
+    /// Take the spans of the blocks in the events and return their paths.
+    fn paths(events: &mut [Event]) -> Vec<PathBuf> {
+        events
+            .iter_mut()
+            .map(|x| x.block.span.take().unwrap().path.unwrap().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn dummy() {
+        let mut dir = DummyDir::with_ctnt([
+            (PathBuf::from("a.md"), b"1\n2\n".to_vec()),
+            (PathBuf::from("empty.md"), vec![]),
+        ]);
+        let mut res = read_dir(&mut dir, Lines)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // Not a file, so the spans are in the relative path
+        assert_eq!(paths(&mut res), [PathBuf::from("a.md"), PathBuf::from("a.md")]);
+        assert_eq!(
+            res,
+            [
+                Event::new_some(Path::new("a.md"), CodeBlock::new("1\n", "", vec![])),
+                Event::new_none(CodeBlock::new("2\n", "", vec![])),
+            ]
+        );
+    }
+
+    #[test]
+    fn tmpdir() {
+        use std::io::Write;
+
+        use tempfile::TempDir;
+
+        use crate::directory::fsdir::FsDir;
+        use crate::directory::Directory;
+        use crate::write_blocks::write_blocks_errless;
+
+        let tmp = TempDir::new().unwrap();
+        let mut src = FsDir::new(tmp.path());
+        src.open_write(Path::new("a/b.md"))
+            .unwrap()
+            .write_all(b"1\n2\n")
+            .unwrap();
+        src.open_write(Path::new("c.md")).unwrap().write_all(b"3\n").unwrap();
+        // The sources read from files are owned by the iterator, so keep it alive
+        let mut it = read_dir(&mut src, Lines).unwrap();
+        let mut res = it.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        // The order of walking is unspecified
+        res.sort_by_key(|x| x.block.content.to_string());
+        assert_eq!(
+            paths(&mut res),
+            [
+                tmp.path().join("a/b.md"),
+                tmp.path().join("a/b.md"),
+                tmp.path().join("c.md")
+            ]
+        );
+        assert_eq!(
+            res,
+            [
+                Event::new_some(Path::new("a/b.md"), CodeBlock::new("1\n", "", vec![])),
+                Event::new_none(CodeBlock::new("2\n", "", vec![])),
+                Event::new_some(Path::new("c.md"), CodeBlock::new("3\n", "", vec![])),
+            ]
+        );
+        let mut out = DummyDir::new();
+        write_blocks_errless(res.into_iter(), &mut out).unwrap();
+        let mut out = out.into_iter().collect::<Vec<_>>();
+        out.sort();
+        assert_eq!(
+            out,
+            [
+                (PathBuf::from("a/b.md"), b"1\n2\n".to_vec()),
+                (PathBuf::from("c.md"), b"3\n".to_vec()),
+            ]
+        );
+        drop(it);
+    }
+}
@@ -0,0 +1,323 @@
+/*!
+Source maps mapping the lines in the output files back to the source documents.
+
+For languages without [line directives](crate::line_directive), e.g. Rust and Python,
+the source maps can be written beside the output files
+(see [`WriteOptions::with_source_maps`](crate::write_blocks::WriteOptions::with_source_maps)),
+and be used to translate the locations in the diagnostics to the locations in the documents.
+*/
+
+
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::codeblock::Span;
+
+
+
+/**
+The error when loading the source map.
+*/
+#[derive(Debug, Error)]
+pub enum LoadError {
+    /// The error when reading the source map file.
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+    /// The line in the source map is malformed.
+    #[error("malformed source map at line {0}")]
+    Malformed(usize),
+}
+
+
+
+/**
+The location in the source document.
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    /// The path to the source document, `None` if unknown.
+    pub path: Option<PathBuf>,
+    /// The line in the source document, `None` if unknown.
+    pub line: Option<usize>,
+    /// The index of the event of the code block in the event iterator written, starting from 0.
+    ///
+    /// It counts the events to all targets, so a code block dispatched to multiple targets,
+    /// e.g. by [`ByAttrs`](crate::dispatch::ByAttrs), has an index for each of them,
+    /// and the index is not the position of the block in the source document.
+    pub block: usize,
+}
+
+/**
+The lines in the output from a code block.
+*/
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    /// The first line in the output.
+    start: usize,
+    /// The line after the last line in the output.
+    end: usize,
+    /// The index of the event of the code block.
+    block: usize,
+    /// The line of the code block in the source document.
+    line: Option<usize>,
+    /// The path to the source document.
+    path: Option<PathBuf>,
+}
+
+/**
+The map from the lines in an output file to the locations in the source documents.
+
+The map is serialized into lines (with [`Display`]), one for each code block in the output,
+in the form of `start end block line path`, separated by tabs,
+where `start` and `end` are the range of the lines in the output (the end is exclusive),
+`block` is the [index of the event](Location::block) of the code block,
+and `line` and `path` are where the block is in the document;
+`line` is `-` and `path` is empty if they are unknown.
+The backslashes, tabs, carriage returns and line breaks in `path` are escaped as `\\`, `\t`, `\r` and `\n`.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::read::LineIndex;
+use scribere::source_map::{Location, SourceMap};
+use std::path::Path;
+
+let mut blk = CodeBlock::new("a\nb\n", "", vec![]);
+blk.with_span(Some(LineIndex::new("\n\na\nb\n").span(Some(Path::new("doc.md").into()), 2..6)));
+let mut map = SourceMap::new();
+map.skip("// Generated\n");
+map.record(0, &blk.content, blk.span.as_ref());
+let map: SourceMap = map.to_string().parse().unwrap();
+assert_eq!(map.lookup(1), None);
+assert_eq!(
+    map.lookup(3),
+    Some(Location { path: Some("doc.md".into()), line: Some(4), block: 0 })
+);
+```
+*/
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourceMap {
+    /// The entries of the blocks, in the order of the lines.
+    entries: Vec<Entry>,
+    /// The number of complete lines recorded in the output.
+    lines: usize,
+    /// Whether the last line recorded is incomplete, i.e. without a line break.
+    partial: bool,
+}
+
+impl SourceMap {
+    /// Create an empty source map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the path to the source map of the output file, i.e. the path with `.map` appended.
+    pub fn sidecar(output: &Path) -> PathBuf {
+        let mut res = output.as_os_str().to_os_string();
+        res.push(".map");
+        res.into()
+    }
+
+    /// Load the source map of the output file from the filesystem.
+    pub fn load(output: &Path) -> Result<Self, LoadError> {
+        std::fs::read_to_string(Self::sidecar(output))?.parse()
+    }
+
+    /// Record that the text not from any code block is written to the output.
+    pub fn skip(&mut self, text: &str) {
+        self.lines += text.matches('\n').count();
+        if !text.is_empty() {
+            self.partial = !text.ends_with('\n');
+        }
+    }
+
+    /// Record that the content of a code block at the span is written to the output,
+    /// as the event of the index in the event iterator written.
+    ///
+    /// If the last line in the output is incomplete,
+    /// the first line of the content would be a part of it, so it's not recorded.
+    pub fn record(&mut self, block: usize, content: &str, span: Option<&Span>) {
+        let first = self.lines + 1;
+        let shared = usize::from(self.partial);
+        let end = first + content.lines().count();
+        self.skip(content);
+        if first + shared < end {
+            self.entries.push(Entry {
+                start: first + shared,
+                end,
+                block,
+                line: span.map(|x| x.line + shared),
+                path: span.and_then(|x| x.path.as_ref()).map(|x| x.to_path_buf()),
+            });
+        }
+    }
+
+    /// Find the location in the source document of the line in the output, starting from 1.
+    pub fn lookup(&self, line: usize) -> Option<Location> {
+        let entry = &self.entries[..self.entries.partition_point(|x| x.start <= line)].last()?;
+        (line < entry.end).then(|| Location {
+            path: entry.path.clone(),
+            line: entry.line.map(|x| x + line - entry.start),
+            block: entry.block,
+        })
+    }
+}
+
+impl Display for SourceMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            write!(f, "{}\t{}\t{}\t", entry.start, entry.end, entry.block)?;
+            match entry.line {
+                Some(line) => write!(f, "{line}\t")?,
+                None => write!(f, "-\t")?,
+            }
+            if let Some(ref path) = entry.path {
+                write!(f, "{}", escape(&path.to_string_lossy()))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Escape the path so that it can be put in a field.
+fn escape(path: &str) -> String {
+    let mut res = String::with_capacity(path.len());
+    for ch in path.chars() {
+        match ch {
+            '\\' => res.push_str("\\\\"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            '\n' => res.push_str("\\n"),
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
+/// Unescape the path in a field, `None` if there is an unknown escape.
+fn unescape(field: &str) -> Option<String> {
+    let mut res = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(ch) = chars.next() {
+        res.push(match ch {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                't' => '\t',
+                'r' => '\r',
+                'n' => '\n',
+                _ => return None,
+            },
+            _ => ch,
+        });
+    }
+    Some(res)
+}
+
+impl std::str::FromStr for SourceMap {
+    type Err = LoadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut res = Self::new();
+        for (i, line) in s.lines().enumerate() {
+            let malformed = || LoadError::Malformed(i + 1);
+            let mut fields = line.splitn(5, '\t');
+            let mut num = || {
+                fields
+                    .next()
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .ok_or_else(malformed)
+            };
+            let (start, end, block) = (num()?, num()?, num()?);
+            let line = match fields.next().ok_or_else(malformed)? {
+                "-" => None,
+                x => Some(x.parse().map_err(|_| malformed())?),
+            };
+            let path = unescape(fields.next().ok_or_else(malformed)?).ok_or_else(malformed)?;
+            if start > end || matches!(res.entries.last(), Some(x) if x.end > start) {
+                return Err(malformed());
+            }
+            res.entries.push(Entry {
+                start,
+                end,
+                block,
+                line,
+                path: (!path.is_empty()).then(|| path.into()),
+            });
+            res.lines = end - 1;
+        }
+        Ok(res)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{Location, SourceMap};
+    use crate::codeblock::Span;
+
+    #[test]
+    fn record_lookup() {
+        let span = Span {
+            path: Some(Path::new("a b.md").into()),
+            start: 0,
+            end: 0,
+            line: 10,
+            column: 1,
+        };
+        let mut map = SourceMap::new();
+        map.record(0, "a\nb", Some(&span));
+        map.record(1, "\nx\n", None);
+        map.record(2, "", None);
+        map.skip("#line\n");
+        map.record(3, "c\n", Some(&span));
+        assert_eq!(
+            map.to_string(),
+            "1\t3\t0\t10\ta b.md\n3\t4\t1\t-\t\n5\t6\t3\t10\ta b.md\n"
+        );
+        let map = map.to_string().parse::<SourceMap>().unwrap();
+        let loc = |path: Option<&str>, line, block| Location {
+            path: path.map(PathBuf::from),
+            line,
+            block,
+        };
+        assert_eq!(map.lookup(0), None);
+        assert_eq!(map.lookup(2), Some(loc(Some("a b.md"), Some(11), 0)));
+        assert_eq!(map.lookup(3), Some(loc(None, None, 1)));
+        assert_eq!(map.lookup(4), None);
+        assert_eq!(map.lookup(5), Some(loc(Some("a b.md"), Some(10), 3)));
+        assert_eq!(map.lookup(6), None);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!("1\t2\t0\t-\n".parse::<SourceMap>().is_err());
+        assert!("1\t2\t0\tx\t\n".parse::<SourceMap>().is_err());
+        assert!("1\t3\t0\t-\t\n2\t3\t1\t-\t\n".parse::<SourceMap>().is_err());
+        assert!("1\t2\t0\t-\ta\\b\n".parse::<SourceMap>().is_err());
+        assert_eq!(SourceMap::sidecar(Path::new("src/a.rs")), Path::new("src/a.rs.map"));
+    }
+
+    #[test]
+    fn escape() {
+        let span = Span {
+            path: Some(Path::new("a\tb\nc\\d\r.md").into()),
+            start: 0,
+            end: 0,
+            line: 1,
+            column: 1,
+        };
+        let mut map = SourceMap::new();
+        map.record(0, "a\n", Some(&span));
+        assert_eq!(map.to_string(), "1\t2\t0\t1\ta\\tb\\nc\\\\d\\r.md\n");
+        let map = map.to_string().parse::<SourceMap>().unwrap();
+        assert_eq!(map.lookup(1).unwrap().path.unwrap(), Path::new("a\tb\nc\\d\r.md"));
+    }
+}
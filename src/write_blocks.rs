@@ -1,15 +1,22 @@
 /*!
-Write all code blocks in the iterator to the directory with the dispatcher.
+Write all code blocks in the iterator to the directory with the dispatcher,
+or collect them in the memory.
 */
 
 
 
+use std::collections::BTreeMap;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use thiserror::Error;
 
-use crate::directory::Directory;
+use crate::codeblock::CodeBlock;
+use crate::directory::{normalize, Directory};
 use crate::dispatch::Event;
+use crate::line_directive::LineDirectives;
+use crate::read::SourceCode;
+use crate::source_map::SourceMap;
 
 
 
@@ -21,6 +28,9 @@ pub enum WriteError<D: std::error::Error, B: std::error::Error> {
     /// The path returned by dispatcher for the first code block is `None`.
     #[error("dispatcher returned `None` for the first code block")]
     NullPath,
+    /// The target escapes the directory, see [`Directory::normalize_path`].
+    #[error("target `{}` escapes the directory", .0.display())]
+    EscapingPath(PathBuf),
     /// The error returned from the directory.
     #[error("directory error: {0}")]
     DirError(D),
@@ -35,58 +45,428 @@ pub enum WriteError<D: std::error::Error, B: std::error::Error> {
 
 
 /**
-Write all code blocks in the iterator according to its dispatching targets.
+The status of a file after writing.
 */
-pub fn write_blocks<'a, Dir: Directory, E: std::error::Error>(
-    mut it: impl Iterator<Item = Result<Event<'a>, E>>,
-    dir: &mut Dir,
-) -> Result<(), WriteError<Dir::OpenError, E>> {
-    let mut event = match it.next() {
-        Some(Ok(e)) => e,
-        Some(Err(e)) => return Err(WriteError::BlockError(e)),
-        None => return Ok(()),
-    };
-    loop {
-        let mut writer = dir
-            .open_append(&event.target.ok_or(WriteError::NullPath)?)
-            .map_err(WriteError::DirError)?;
-        loop {
-            writer
-                .write_all(event.block.content.as_bytes())
-                .map_err(WriteError::IOError)?;
-            match it.next() {
-                Some(Ok(e)) => {
-                    event = e;
-                    if event.target.is_some() {
-                        break;
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileStatus {
+    /// The file is written without comparing with its previous content.
+    Written,
+    /// The file didn't exist and is created.
+    Created,
+    /// The file existed and is updated with new content.
+    Updated,
+    /// The file existed with the same content, so it's not written.
+    Unchanged,
+}
+
+
+
+/**
+The options of writing code blocks.
+
+The default options simply write the content of the code blocks;
+and [`write_blocks`] is a shorthand for writing with them.
+
+In each writing, a file is truncated when it's opened for the first time,
+and is appended to when the target switches back to it later,
+so the previous content of the files would be replaced.
+This also makes it possible to write a code block to multiple files,
+by expanding it into consecutive events with different targets,
+see [`ByAttrs`](crate::dispatch::ByAttrs) for example.
+
+The targets are [normalized](Directory::normalize_path) by the directory before opened,
+and [`WriteError::EscapingPath`] is returned if a target is absolute or escapes the directory,
+so that the documents can never write outside the directory.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::directory::dummydir::DummyDir;
+use scribere::dispatch::{DispatchErrless, MonoFile};
+use scribere::line_directive::LineDirectives;
+use scribere::read::LineIndex;
+use scribere::write_blocks::WriteOptions;
+use std::path::Path;
+
+let mut blk = CodeBlock::new("int a;\n", "c", vec![]);
+blk.with_span(Some(LineIndex::new("\n\nint a;\n").span(Some(Path::new("doc.md").into()), 2..9)));
+let mut dir = DummyDir::new();
+let directives = LineDirectives::new();
+WriteOptions::new()
+    .with_line_directives(Some(&directives))
+    .write_errless(MonoFile::new(Path::new("a.c")).dispatch([blk].into_iter()), &mut dir)
+    .unwrap();
+assert_eq!(
+    dir.into_iter().collect::<Vec<_>>(),
+    [(Path::new("a.c").to_path_buf(), b"#line 3 \"doc.md\"\nint a;\n".to_vec())]
+);
+```
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteOptions<'o> {
+    /// The line directives written before the code blocks.
+    line_directives: Option<&'o LineDirectives<'o>>,
+    /// Whether to write the source maps beside the outputs.
+    source_maps: bool,
+    /// Whether to write only the files whose content changed.
+    only_changed: bool,
+}
+
+impl<'o> WriteOptions<'o> {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the line directives written before each code block and return self.
+    ///
+    /// The directive is written only when the block has one, see [`LineDirectives::directive`].
+    /// If a block has no directive but the previous one in the file has,
+    /// a directive in the language of the previous one is written to map the following lines back to the file itself,
+    /// e.g. `#line 5 "a.c"` with the target as the path, so the diagnostics never point to the wrong document.
+    /// A line break is inserted before the directive if the last block in the file doesn't end with one.
+    pub fn with_line_directives(&mut self, directives: Option<&'o LineDirectives<'o>>) -> &mut Self {
+        self.line_directives = directives;
+        self
+    }
+
+    /// Set whether to write the source map of each output file beside it and return self.
+    ///
+    /// The source maps are written after all code blocks are written,
+    /// to the paths given by [`SourceMap::sidecar`], see [its document](SourceMap) for more.
+    pub fn with_source_maps(&mut self, source_maps: bool) -> &mut Self {
+        self.source_maps = source_maps;
+        self
+    }
+
+    /// Set whether to write only the files whose content changed and return self.
+    ///
+    /// When set, the full content of each file is collected in the memory first,
+    /// and then compared with the existing file,
+    /// so that the files are written only when they would be changed,
+    /// and their modification times are kept otherwise.
+    pub fn with_only_changed(&mut self, only_changed: bool) -> &mut Self {
+        self.only_changed = only_changed;
+        self
+    }
+
+    /// Write all code blocks in the iterator according to its dispatching targets.
+    ///
+    /// Returns the status of all files written, see [`FileStatus`] for more.
+    pub fn write<'a, Dir: Directory, E: std::error::Error>(
+        &self,
+        it: impl Iterator<Item = Result<Event<'a>, E>>,
+        dir: &mut Dir,
+    ) -> Result<BTreeMap<PathBuf, FileStatus>, WriteError<Dir::OpenError, E>> {
+        if !self.only_changed {
+            return self.write_to(it, dir);
+        }
+        let mut buf = Buffer {
+            dir,
+            files: BTreeMap::new(),
+        };
+        self.write_to(it, &mut buf)?;
+        let Buffer { dir, files } = buf;
+        let mut res = BTreeMap::new();
+        // The paths are already normalized by the directory
+        for (path, ctnt) in files {
+            let status = match dir.read_file(&path).map_err(WriteError::DirError)? {
+                None => FileStatus::Created,
+                Some(x) if x == ctnt => FileStatus::Unchanged,
+                Some(_) => FileStatus::Updated,
+            };
+            if status != FileStatus::Unchanged {
+                dir.open_write(&path)
+                    .map_err(WriteError::DirError)?
+                    .write_all(&ctnt)
+                    .map_err(WriteError::IOError)?;
+            }
+            res.insert(path, status);
+        }
+        Ok(res)
+    }
+
+    /// Write all code blocks in the errorless iterator according to its dispatching targets.
+    ///
+    /// Returns the status of all files written, see [`FileStatus`] for more.
+    pub fn write_errless<'a, Dir: Directory>(
+        &self,
+        it: impl Iterator<Item = Event<'a>>,
+        dir: &mut Dir,
+    ) -> Result<BTreeMap<PathBuf, FileStatus>, WriteError<Dir::OpenError, !>> {
+        self.write(it.map(Ok), dir)
+    }
+
+    /// Write the code blocks to the directory directly.
+    fn write_to<'a, Dir: Directory, E: std::error::Error>(
+        &self,
+        mut it: impl Iterator<Item = Result<Event<'a>, E>>,
+        dir: &mut Dir,
+    ) -> Result<BTreeMap<PathBuf, FileStatus>, WriteError<Dir::OpenError, E>> {
+        let mut res = BTreeMap::new();
+        let mut event = match it.next() {
+            Some(Ok(e)) => e,
+            Some(Err(e)) => return Err(WriteError::BlockError(e)),
+            None => return Ok(res),
+        };
+        let mut maps = BTreeMap::<PathBuf, SourceMap>::new();
+        let mut states = BTreeMap::<PathBuf, TargetState>::new();
+        let mut index = 0;
+        'targets: loop {
+            let target = event.target.ok_or(WriteError::NullPath)?;
+            let target = dir
+                .normalize_path(&target)
+                .ok_or_else(|| WriteError::EscapingPath(target.into_owned()))?;
+            // Truncate the file at the first time it's opened, and append to it later
+            let writer = match res.insert(target.clone(), FileStatus::Written) {
+                None => dir.open_write(&target),
+                Some(_) => dir.open_append(&target),
+            };
+            let mut writer = writer.map_err(WriteError::DirError)?;
+            let state = states.entry(target.clone()).or_default();
+            let mut map = self.source_maps.then(|| maps.entry(target.clone()).or_default());
+            loop {
+                let directive = self.line_directives.and_then(|x| match x.directive(&event.block) {
+                    Some(directive) => {
+                        state.lang = Some(event.block.lang.to_string());
+                        Some(directive)
                     }
+                    // Map the lines after the previous directive back to the output itself
+                    None => {
+                        let next = state.lines + if state.partial { 3 } else { 2 };
+                        x.directive_at(&state.lang.take()?, next, &target)
+                    }
+                });
+                if let Some(directive) = directive {
+                    // The directive must start a line of its own
+                    let text = format!("{}{directive}\n", if state.partial { "\n" } else { "" });
+                    writer.write_all(text.as_bytes()).map_err(WriteError::IOError)?;
+                    if let Some(ref mut map) = map {
+                        map.skip(&text);
+                    }
+                    state.wrote(&text);
+                }
+                writer
+                    .write_all(event.block.content.as_bytes())
+                    .map_err(WriteError::IOError)?;
+                if let Some(ref mut map) = map {
+                    map.record(index, &event.block.content, event.block.span.as_ref());
+                }
+                state.wrote(&event.block.content);
+                index += 1;
+                match it.next() {
+                    Some(Ok(e)) => {
+                        event = e;
+                        if event.target.is_some() {
+                            break;
+                        }
+                    }
+                    Some(Err(e)) => return Err(WriteError::BlockError(e)),
+                    None => break 'targets,
                 }
-                Some(Err(e)) => return Err(WriteError::BlockError(e)),
-                None => return Ok(()),
             }
         }
+        for (target, map) in maps {
+            let sidecar = SourceMap::sidecar(&target);
+            let sidecar = dir.normalize_path(&sidecar).ok_or(WriteError::EscapingPath(sidecar))?;
+            write!(dir.open_write(&sidecar).map_err(WriteError::DirError)?, "{map}").map_err(WriteError::IOError)?;
+            res.insert(sidecar, FileStatus::Written);
+        }
+        Ok(res)
+    }
+}
+
+/**
+The state of a target during writing, for the line directives.
+*/
+#[derive(Debug, Clone, Default)]
+struct TargetState {
+    /// The number of line breaks written.
+    lines: usize,
+    /// Whether the last line written is incomplete, i.e. without a line break.
+    partial: bool,
+    /// The language of the last directive written,
+    /// `None` if the lines written since are not mapped to the documents.
+    lang: Option<String>,
+}
+
+impl TargetState {
+    /// Update the state after writing the text.
+    fn wrote(&mut self, text: &str) {
+        self.lines += text.matches('\n').count();
+        if !text.is_empty() {
+            self.partial = !text.ends_with('\n');
+        }
+    }
+}
+
+/**
+The contents of the files collected in the memory before written to the directory,
+with the paths normalized by the directory.
+*/
+struct Buffer<'d, D: Directory> {
+    /// The directory written to finally.
+    dir: &'d mut D,
+    /// The contents of the files.
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl<D: Directory> Directory for Buffer<'_, D> {
+    type DirIter<'a>
+        = std::iter::Empty<Result<(PathBuf, SourceCode<'a, 'a>), Self::WalkError>>
+    where
+        Self: 'a;
+    type OpenError = D::OpenError;
+    type WalkError = D::WalkError;
+    type Writer<'a>
+        = &'a mut Vec<u8>
+    where
+        Self: 'a;
+
+    fn open_append<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        Ok(self.files.entry(path.to_path_buf()).or_default())
     }
+
+    fn open_write<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+        let res = self.files.entry(path.to_path_buf()).or_default();
+        res.clear();
+        Ok(res)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Self::OpenError> {
+        Ok(self.files.get(path).cloned())
+    }
+
+    /// The buffer is never walked, so it's always empty.
+    fn walk(&self) -> Result<Self::DirIter<'_>, Self::OpenError> {
+        Ok(std::iter::empty())
+    }
+
+    fn normalize_path(&self, path: &Path) -> Option<PathBuf> {
+        self.dir.normalize_path(path)
+    }
+}
+
+/**
+Write all code blocks in the iterator according to its dispatching targets.
+
+This is the same as writing with the default [`WriteOptions`].
+*/
+pub fn write_blocks<'a, Dir: Directory, E: std::error::Error>(
+    it: impl Iterator<Item = Result<Event<'a>, E>>,
+    dir: &mut Dir,
+) -> Result<(), WriteError<Dir::OpenError, E>> {
+    WriteOptions::new().write(it, dir).map(drop)
 }
 
 /**
 Write all code blocks in the errorless iterator according to its dispatching targets.
+
+This is the same as writing with the default [`WriteOptions`].
 */
 pub fn write_blocks_errless<'a, Dir: Directory>(
     it: impl Iterator<Item = Event<'a>>,
     dir: &mut Dir,
 ) -> Result<(), WriteError<Dir::OpenError, !>> {
-    write_blocks(it.map(Ok), dir)
+    WriteOptions::new().write_errless(it, dir).map(drop)
+}
+
+/**
+Collect all code blocks in the iterator into the map from their targets to the blocks.
+
+The semantics is the same as [`write_blocks`]: the blocks are in the order of the iterator in each target,
+[`WriteError::NullPath`] is returned if the first block lacks a target,
+and the targets are [normalized lexically](crate::directory::normalize),
+with [`WriteError::EscapingPath`] returned if a target escapes.
+
+Example:
+
+```
+use scribere::CodeBlock;
+use scribere::dispatch::{ByAttr, DispatchErrless};
+use scribere::write_blocks::collect_contents_errless;
+use std::path::PathBuf;
+
+let ctnt = [
+    CodeBlock::new("1\n", "", vec![("file".into(), "./a".into())]),
+    CodeBlock::new("2\n", "", vec![("file".into(), "b".into())]),
+    CodeBlock::new("3\n", "", vec![("file".into(), "a".into())]),
+];
+assert_eq!(
+    collect_contents_errless(ByAttr::new("file").dispatch(ctnt.into_iter())).unwrap(),
+    [(PathBuf::from("a"), "1\n3\n".to_string()), (PathBuf::from("b"), "2\n".to_string())].into()
+);
+```
+*/
+pub fn collect_blocks<'a, E: std::error::Error>(
+    it: impl Iterator<Item = Result<Event<'a>, E>>,
+) -> Result<BTreeMap<PathBuf, Vec<CodeBlock<'a>>>, WriteError<!, E>> {
+    let mut res = BTreeMap::<PathBuf, Vec<_>>::new();
+    let mut last = None;
+    for event in it {
+        let event = event.map_err(WriteError::BlockError)?;
+        if let Some(target) = event.target {
+            let path = normalize(&target).ok_or_else(|| WriteError::EscapingPath(target.into_owned()))?;
+            last = Some(path);
+        }
+        let target = last.as_ref().ok_or(WriteError::NullPath)?;
+        res.entry(target.clone()).or_default().push(event.block);
+    }
+    Ok(res)
+}
+
+/**
+Collect all code blocks in the errorless iterator into the map from their targets to the blocks.
+
+See [`collect_blocks`] for more.
+*/
+pub fn collect_blocks_errless<'a>(
+    it: impl Iterator<Item = Event<'a>>,
+) -> Result<BTreeMap<PathBuf, Vec<CodeBlock<'a>>>, WriteError<!, !>> {
+    collect_blocks(it.map(Ok))
+}
+
+/**
+Collect the content of all code blocks in the iterator into the map from their targets to the contents.
+
+This is the same as what [`write_blocks`] writes, see [`collect_blocks`] for more.
+*/
+pub fn collect_contents<'a, E: std::error::Error>(
+    it: impl Iterator<Item = Result<Event<'a>, E>>,
+) -> Result<BTreeMap<PathBuf, String>, WriteError<!, E>> {
+    Ok(collect_blocks(it)?
+        .into_iter()
+        .map(|(k, v)| (k, v.iter().map(|x| x.content.as_ref()).collect()))
+        .collect())
+}
+
+/**
+Collect the content of all code blocks in the errorless iterator into the map from their targets to the contents.
+
+See [`collect_contents`] for more.
+*/
+pub fn collect_contents_errless<'a>(
+    it: impl Iterator<Item = Event<'a>>,
+) -> Result<BTreeMap<PathBuf, String>, WriteError<!, !>> {
+    collect_contents(it.map(Ok))
 }
 
 
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::collections::{BTreeMap, HashMap};
+    use std::path::{Path, PathBuf};
 
+    use super::{collect_blocks_errless, collect_contents_errless, FileStatus, WriteError, WriteOptions};
     use crate::codeblock::CodeBlock;
     use crate::directory::dummydir::DummyDir;
-    use crate::dispatch::{ByAttr, DispatchErrless};
+    use crate::directory::Directory;
+    use crate::dispatch::{ByAttr, ByAttrs, DispatchErrless, Event, MonoFile};
+    use crate::line_directive::LineDirectives;
+    use crate::read::LineIndex;
+    use crate::source_map::{Location, SourceMap};
     use crate::write_blocks::write_blocks_errless;
 
     #[test]
@@ -96,26 +476,31 @@ mod tests {
                 lang: "".into(),
                 content: "block 1\n".into(),
                 attrs: vec![("a".into(), "hello".into())],
+                span: None,
             },
             CodeBlock {
                 lang: "".into(),
                 content: "block 2\n".into(),
                 attrs: vec![("b".into(), "hello".into())],
+                span: None,
             },
             CodeBlock {
                 lang: "".into(),
                 content: "block 3\n".into(),
                 attrs: vec![("a".into(), "hi".into())],
+                span: None,
             },
             CodeBlock {
                 lang: "".into(),
                 content: "block 4\n".into(),
                 attrs: vec![("a".into(), "hello".into())],
+                span: None,
             },
             CodeBlock {
                 lang: "".into(),
                 content: "block 5\n".into(),
                 attrs: vec![("b".into(), "hello".into())],
+                span: None,
             },
         ]
         .into_iter();
@@ -134,4 +519,260 @@ mod tests {
     fn empty_blocks() {
         write_blocks_errless(&mut ByAttr::new("a").dispatch([].into_iter()), &mut DummyDir::new()).unwrap();
     }
+
+    #[test]
+    fn line_directives() {
+        let src = "```c\nint a;\n```\n```rust\nlet a = 1;\n```\n```c\nint b;\n```\n";
+        let idx = LineIndex::new(src);
+        let blocks = [(5, 12, "c"), (24, 35, "rust"), (44, 51, "c")].map(|(start, end, lang)| {
+            let mut blk = CodeBlock::new(&src[start..end], lang, vec![]);
+            blk.with_span(Some(idx.span(Some(Path::new("doc.md").into()), start..end)));
+            blk
+        });
+        let mut dir = DummyDir::new();
+        let directives = LineDirectives::new();
+        WriteOptions::new()
+            .with_line_directives(Some(&directives))
+            .write_errless(MonoFile::new(Path::new("a")).dispatch(blocks.into_iter()), &mut dir)
+            .unwrap();
+        assert_eq!(
+            dir.into_iter().collect::<Vec<_>>(),
+            [(
+                PathBuf::from("a"),
+                b"#line 2 \"doc.md\"\nint a;\n#line 4 \"a\"\nlet a = 1;\n#line 8 \"doc.md\"\nint b;\n".to_vec()
+            )]
+        );
+    }
+
+    #[test]
+    fn unterminated_block() {
+        let src = "```c\nint a;\n```\n```c\nint b;\n```\n";
+        let idx = LineIndex::new(src);
+        let blocks = [(5, 11), (21, 28)].map(|(start, end)| {
+            let mut blk = CodeBlock::new(&src[start..end], "c", vec![]);
+            blk.with_span(Some(idx.span(Some(Path::new("doc.md").into()), start..end)));
+            blk
+        });
+        let mut dir = DummyDir::new();
+        let directives = LineDirectives::new();
+        WriteOptions::new()
+            .with_line_directives(Some(&directives))
+            .with_source_maps(true)
+            .write_errless(MonoFile::new(Path::new("a")).dispatch(blocks.into_iter()), &mut dir)
+            .unwrap();
+        let dir = dir.into_iter().collect::<HashMap<_, _>>();
+        assert_eq!(
+            dir[Path::new("a")],
+            b"#line 2 \"doc.md\"\nint a;\n#line 5 \"doc.md\"\nint b;\n"
+        );
+        let map = std::str::from_utf8(&dir[&SourceMap::sidecar(Path::new("a"))])
+            .unwrap()
+            .parse::<SourceMap>()
+            .unwrap();
+        let loc = |line, block| Location {
+            path: Some("doc.md".into()),
+            line: Some(line),
+            block,
+        };
+        assert_eq!(map.lookup(2), Some(loc(2, 0)));
+        assert_eq!(map.lookup(3), None);
+        assert_eq!(map.lookup(4), Some(loc(5, 1)));
+    }
+
+    #[test]
+    fn source_maps() {
+        let src = "```c\nint a;\n```\n```rust\nlet a = 1;\nlet b = 2;\n```\n```c\nint b;\n```\n";
+        let idx = LineIndex::new(src);
+        let blocks = [(5, 12, "c"), (24, 46, "rs"), (55, 62, "c")].map(|(start, end, lang)| {
+            let file = format!("a.{lang}");
+            let mut blk = CodeBlock::new(&src[start..end], lang, vec![("file".into(), file.into())]);
+            blk.with_span(Some(idx.span(Some(Path::new("doc.md").into()), start..end)));
+            blk
+        });
+        let mut dir = DummyDir::new();
+        let directives = LineDirectives::new();
+        WriteOptions::new()
+            .with_line_directives(Some(&directives))
+            .with_source_maps(true)
+            .write_errless(ByAttr::new("file").dispatch(blocks.into_iter()), &mut dir)
+            .unwrap();
+        let dir = dir.into_iter().collect::<HashMap<_, _>>();
+        let map = |path: &str| {
+            std::str::from_utf8(&dir[&SourceMap::sidecar(Path::new(path))])
+                .unwrap()
+                .parse::<SourceMap>()
+                .unwrap()
+        };
+        let loc = |line, block| Location {
+            path: Some("doc.md".into()),
+            line: Some(line),
+            block,
+        };
+        let (c, rs) = (map("a.c"), map("a.rs"));
+        assert_eq!(c.lookup(1), None);
+        assert_eq!(c.lookup(2), Some(loc(2, 0)));
+        assert_eq!(c.lookup(3), None);
+        assert_eq!(c.lookup(4), Some(loc(9, 2)));
+        assert_eq!(rs.lookup(2), Some(loc(6, 1)));
+        assert_eq!(rs.lookup(3), None);
+    }
+
+    #[test]
+    fn source_map_indices() {
+        let src = "1\n2\n";
+        let idx = LineIndex::new(src);
+        let blocks = [(0, 2, "a, b"), (2, 4, "a")].map(|(start, end, file)| {
+            let mut blk = CodeBlock::new(&src[start..end], "", vec![("file".into(), file.into())]);
+            blk.with_span(Some(idx.span(Some(Path::new("doc.md").into()), start..end)));
+            blk
+        });
+        let mut dir = DummyDir::new();
+        WriteOptions::new()
+            .with_source_maps(true)
+            .write_errless(ByAttrs::new("file").dispatch(blocks.into_iter()), &mut dir)
+            .unwrap();
+        let dir = dir.into_iter().collect::<HashMap<_, _>>();
+        let map = std::str::from_utf8(&dir[Path::new("a.map")])
+            .unwrap()
+            .parse::<SourceMap>()
+            .unwrap();
+        // The first block is written as the events 0 and 1, so the second one is the event 2
+        let loc = |line, block| Location {
+            path: Some("doc.md".into()),
+            line: Some(line),
+            block,
+        };
+        assert_eq!(map.lookup(1), Some(loc(1, 0)));
+        assert_eq!(map.lookup(2), Some(loc(2, 2)));
+    }
+
+    #[test]
+    fn only_changed() {
+        let ctnt = [
+            CodeBlock::new("same\n", "", vec![("a".into(), "same".into())]),
+            CodeBlock::new("new\n", "", vec![("a".into(), "changed".into())]),
+            CodeBlock::new("new\n", "", vec![("a".into(), "created".into())]),
+        ];
+        let mut dir = DummyDir::with_ctnt([
+            (PathBuf::from("same"), b"same\n".to_vec()),
+            (PathBuf::from("changed"), b"old\n".to_vec()),
+        ]);
+        let res = WriteOptions::new()
+            .with_only_changed(true)
+            .write_errless(ByAttr::new("a").dispatch(ctnt.into_iter()), &mut dir)
+            .unwrap();
+        assert_eq!(
+            res.into_iter().collect::<Vec<_>>(),
+            [
+                (PathBuf::from("changed"), FileStatus::Updated),
+                (PathBuf::from("created"), FileStatus::Created),
+                (PathBuf::from("same"), FileStatus::Unchanged),
+            ]
+        );
+        let mut dir = dir.into_iter().collect::<Vec<_>>();
+        dir.sort();
+        assert_eq!(
+            dir,
+            [
+                (PathBuf::from("changed"), b"new\n".to_vec()),
+                (PathBuf::from("created"), b"new\n".to_vec()),
+                (PathBuf::from("same"), b"same\n".to_vec()),
+            ]
+        );
+    }
+
+    /// A directory implementing only the required methods, truncating the files when opened.
+    #[derive(Debug, Default)]
+    struct Truncating(DummyDir);
+
+    impl Directory for Truncating {
+        type DirIter<'a> = <DummyDir as Directory>::DirIter<'a>;
+        type OpenError = !;
+        type WalkError = <DummyDir as Directory>::WalkError;
+        type Writer<'a> = &'a mut Vec<u8>;
+
+        fn open_append<'a>(&'a mut self, path: &Path) -> Result<Self::Writer<'a>, Self::OpenError> {
+            self.0.open_write(path)
+        }
+
+        fn walk(&self) -> Result<Self::DirIter<'_>, Self::OpenError> {
+            self.0.walk()
+        }
+    }
+
+    #[test]
+    fn default_methods() {
+        let ctnt = [CodeBlock::new("same\n", "", vec![("a".into(), "same".into())])];
+        let mut dir = Truncating::default();
+        dir.open_append(Path::new("same")).unwrap();
+        let res = WriteOptions::new()
+            .with_only_changed(true)
+            .write_errless(ByAttr::new("a").dispatch(ctnt.into_iter()), &mut dir)
+            .unwrap();
+        // The files can't be read, so they are always created
+        assert_eq!(
+            res.into_iter().collect::<Vec<_>>(),
+            [(PathBuf::from("same"), FileStatus::Created)]
+        );
+        assert_eq!(
+            dir.0.into_iter().collect::<Vec<_>>(),
+            [(PathBuf::from("same"), b"same\n".to_vec())]
+        );
+    }
+
+    #[test]
+    fn escaping_path() {
+        let ctnt = [
+            CodeBlock::new("1\n", "", vec![("a".into(), "./x/../y".into())]),
+            CodeBlock::new("2\n", "", vec![("a".into(), "../../etc/x".into())]),
+        ];
+        let mut dir = DummyDir::new();
+        let res = write_blocks_errless(ByAttr::new("a").dispatch(ctnt.clone().into_iter()), &mut dir);
+        assert!(matches!(res, Err(WriteError::EscapingPath(x)) if x == Path::new("../../etc/x")));
+        assert_eq!(
+            dir.into_iter().collect::<Vec<_>>(),
+            [(PathBuf::from("y"), b"1\n".to_vec())]
+        );
+        let ctnt = [CodeBlock::new("1\n", "", vec![("a".into(), "/abs".into())])];
+        let res = WriteOptions::new()
+            .with_only_changed(true)
+            .write_errless(ByAttr::new("a").dispatch(ctnt.into_iter()), &mut DummyDir::new());
+        assert!(matches!(res, Err(WriteError::EscapingPath(x)) if x == Path::new("/abs")));
+    }
+
+    #[test]
+    fn collect() {
+        let ctnt = [
+            Event::new_some(Path::new("a"), CodeBlock::new("1\n", "", vec![])),
+            Event::new_some(Path::new("b/../c"), CodeBlock::new("2\n", "", vec![])),
+            Event::new_none(CodeBlock::new("3\n", "", vec![])),
+            Event::new_some(Path::new("a"), CodeBlock::new("4\n", "", vec![])),
+        ];
+        let blocks = collect_blocks_errless(ctnt.clone().into_iter()).unwrap();
+        assert_eq!(
+            blocks.into_iter().collect::<Vec<_>>(),
+            [
+                (
+                    PathBuf::from("a"),
+                    vec![CodeBlock::new("1\n", "", vec![]), CodeBlock::new("4\n", "", vec![])]
+                ),
+                (
+                    PathBuf::from("c"),
+                    vec![CodeBlock::new("2\n", "", vec![]), CodeBlock::new("3\n", "", vec![])]
+                ),
+            ]
+        );
+        let mut dir = DummyDir::new();
+        write_blocks_errless(ctnt.clone().into_iter(), &mut dir).unwrap();
+        let contents = collect_contents_errless(ctnt.into_iter()).unwrap();
+        assert_eq!(
+            dir.into_iter().collect::<BTreeMap<_, _>>(),
+            contents.into_iter().map(|(k, v)| (k, v.into_bytes())).collect()
+        );
+        let res = collect_blocks_errless([Event::new_none(CodeBlock::new("1", "", vec![]))].into_iter());
+        assert!(matches!(res, Err(WriteError::NullPath)));
+        let res =
+            collect_blocks_errless([Event::new_some(Path::new("../a"), CodeBlock::new("1", "", vec![]))].into_iter());
+        assert!(matches!(res, Err(WriteError::EscapingPath(_))));
+    }
 }